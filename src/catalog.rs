@@ -0,0 +1,550 @@
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::db::{connect, ConnectionConfig};
+use crate::sql_templates::{
+    mapping_match_condition, quote_literal, MetadataNames, MissingRolePolicy, PresetRoles, CLEANUP_FUNCTION_MARKER,
+    CONFIG_TABLE_MARKER, MAPPING_PRECEDENCE, TRIGGER_FUNCTION_MARKER,
+};
+
+/// PostgreSQL core and cloud provider (AWS RDS, Azure, GCP) system databases,
+/// which are never managed.
+pub const BLOCKED_DATABASES: [&str; 6] = ["postgres", "template0", "template1", "rdsadmin", "azure_maintenance", "cloudsqladmin"];
+
+/// Lists the non-template, non-system databases of the instance.
+pub async fn user_databases(client: &Client, verbose: u8) -> Result<Vec<String>> {
+    let sql = "SELECT datname FROM pg_database WHERE datistemplate = false ORDER BY datname";
+    if verbose >= 1 {
+        println!("[SQL] {}", sql);
+    }
+    let rows = client.query(sql, &[])
+        .await
+        .context("Failed to query pg_database")?;
+
+    Ok(rows
+        .iter()
+        .map(|row| row.get(0))
+        .filter(|dbname: &String| !BLOCKED_DATABASES.contains(&dbname.as_str()))
+        .collect())
+}
+
+/// Lists the template databases that accept connections, leaving out
+/// template0 and template1.
+pub async fn template_databases(client: &Client, verbose: u8) -> Result<Vec<String>> {
+    let sql = "SELECT datname FROM pg_database WHERE datistemplate AND datallowconn ORDER BY datname";
+    if verbose >= 1 {
+        println!("[SQL] {}", sql);
+    }
+    let rows = client.query(sql, &[])
+        .await
+        .context("Failed to query pg_database")?;
+
+    Ok(rows
+        .iter()
+        .map(|row| row.get(0))
+        .filter(|dbname: &String| !BLOCKED_DATABASES.contains(&dbname.as_str()))
+        .collect())
+}
+
+/// Locates the config table, trigger function and event trigger installed by `init`.
+///
+/// Objects are found through the marker comments `init` attaches to them. Databases
+/// initialized before the location was configurable have no comments, so the legacy
+/// `public.schema_ownership_config` table and its function are recognized by name.
+/// Returns `None` when the database has no config table.
+pub async fn discover_metadata(client: &Client, verbose: u8) -> Result<Option<MetadataNames>> {
+    let defaults = MetadataNames::default();
+
+    let sql = "
+        SELECT n.nspname::text, c.relname::text
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('r', 'p')
+          AND (obj_description(c.oid, 'pg_class') = $1
+               OR (n.nspname = 'public' AND c.relname = $2))
+        ORDER BY obj_description(c.oid, 'pg_class') = $1 DESC NULLS LAST
+        LIMIT 1
+    ";
+    if verbose >= 2 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), CONFIG_TABLE_MARKER, defaults.config_table);
+    }
+    let table_row = client
+        .query_opt(sql, &[&CONFIG_TABLE_MARKER, &defaults.config_table])
+        .await
+        .context("Failed to look up config table")?;
+
+    let Some(table_row) = table_row else {
+        return Ok(None);
+    };
+
+    let mut names = MetadataNames {
+        schema: table_row.get(0),
+        config_table: table_row.get(1),
+        ..defaults.clone()
+    };
+
+    // Functions are always installed next to the config table
+    if let Some((function, trigger)) =
+        find_event_function(client, &names.schema, TRIGGER_FUNCTION_MARKER, &defaults.function, verbose).await?
+    {
+        names.function = function;
+        if let Some(trigger) = trigger {
+            names.event_trigger = trigger;
+        }
+    }
+
+    if let Some((function, trigger)) =
+        find_event_function(client, &names.schema, CLEANUP_FUNCTION_MARKER, &defaults.cleanup_function, verbose).await?
+    {
+        names.cleanup_function = function;
+        if let Some(trigger) = trigger {
+            names.cleanup_event_trigger = trigger;
+        }
+    }
+
+    Ok(Some(names))
+}
+
+/// Finds an event trigger function in `schema` by marker comment (or legacy name),
+/// returning its name and the event trigger that calls it, if any.
+async fn find_event_function(
+    client: &Client,
+    schema: &str,
+    marker: &str,
+    legacy_name: &str,
+    verbose: u8,
+) -> Result<Option<(String, Option<String>)>> {
+    let sql = "
+        SELECT p.proname::text, e.evtname::text
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        LEFT JOIN pg_event_trigger e ON e.evtfoid = p.oid
+        WHERE p.prorettype = 'event_trigger'::regtype
+          AND n.nspname = $3
+          AND (obj_description(p.oid, 'pg_proc') = $1 OR p.proname = $2)
+        ORDER BY obj_description(p.oid, 'pg_proc') = $1 DESC NULLS LAST, e.evtname NULLS LAST
+        LIMIT 1
+    ";
+    if verbose >= 2 {
+        println!("[SQL] {} -- params: [{}, {}, {}]", sql.trim(), marker, legacy_name, schema);
+    }
+    let row = client
+        .query_opt(sql, &[&marker, &legacy_name, &schema])
+        .await
+        .context("Failed to look up event trigger function")?;
+    Ok(row.map(|row| (row.get(0), row.get(1))))
+}
+
+/// A row of the config table.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    pub schema_name: String,
+    pub target_role: String,
+    pub active: bool,
+    pub on_missing_role: String,
+    pub role_exists: bool,
+    /// A schema named `schema_name` exists; says nothing about pattern mappings
+    pub schema_exists: bool,
+    /// When and by whom the `sql_drop` trigger deactivated the mapping
+    pub dropped_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub dropped_by: Option<String>,
+    /// `schema_name` is a LIKE pattern rather than a single schema
+    pub is_pattern: bool,
+    pub report_extension_owner: bool,
+    /// Roles whose `FOR ROLE` default privileges the tool added; `None` on
+    /// installations that predate the record
+    pub default_privilege_grantors: Option<Vec<String>>,
+    /// Companion roles recorded by `init --preset`
+    pub preset: Option<PresetRoles>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Reads all mappings. Columns added after the first release are read through
+/// `to_jsonb` so config tables from older installations still load.
+pub async fn load_mappings(client: &Client, metadata: &MetadataNames, verbose: u8) -> Result<Vec<Mapping>> {
+    let sql = format!(
+        "SELECT c.schema_name::text, c.target_role::text,
+                COALESCE((to_jsonb(c) ->> 'active')::boolean, true),
+                COALESCE(to_jsonb(c) ->> 'on_missing_role', {default_policy}),
+                EXISTS (SELECT 1 FROM pg_roles r WHERE r.rolname = c.target_role),
+                COALESCE((to_jsonb(c) ->> 'is_pattern')::boolean, false),
+                COALESCE((to_jsonb(c) ->> 'report_extension_owner')::boolean,
+                         (to_jsonb(c) ->> 'transfer_extension_owner')::boolean, false),
+                CASE WHEN jsonb_typeof(to_jsonb(c) -> 'default_privilege_grantors') = 'array'
+                     THEN ARRAY(SELECT jsonb_array_elements_text(to_jsonb(c) -> 'default_privilege_grantors'))
+                END,
+                to_jsonb(c) ->> 'migrator_role', to_jsonb(c) ->> 'runtime_role', to_jsonb(c) ->> 'readonly_role',
+                c.created_at, c.updated_at,
+                EXISTS (SELECT 1 FROM pg_namespace n WHERE n.nspname = c.schema_name),
+                (to_jsonb(c) ->> 'dropped_at')::timestamptz, to_jsonb(c) ->> 'dropped_by'
+         FROM {} c
+         ORDER BY c.schema_name",
+        metadata.qualified_config_table(),
+        default_policy = quote_literal(MissingRolePolicy::DEFAULT.as_str())
+    );
+    if verbose >= 1 {
+        println!("[SQL] {}", sql);
+    }
+    let rows = client.query(&sql, &[])
+        .await
+        .context("Failed to read schema mappings")?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Mapping {
+            schema_name: row.get(0),
+            target_role: row.get(1),
+            active: row.get(2),
+            on_missing_role: row.get(3),
+            role_exists: row.get(4),
+            schema_exists: row.get(13),
+            dropped_at: row.get(14),
+            dropped_by: row.get(15),
+            is_pattern: row.get(5),
+            report_extension_owner: row.get(6),
+            default_privilege_grantors: row.get(7),
+            preset: match (row.get(8), row.get(9), row.get(10)) {
+                (Some(migrator), Some(runtime), Some(readonly)) => Some(PresetRoles { migrator, runtime, readonly }),
+                _ => None,
+            },
+            created_at: row.get(11),
+            updated_at: row.get(12),
+        })
+        .collect())
+}
+
+/// The installation and mappings of one database.
+#[derive(Debug)]
+pub struct DatabaseMappings {
+    pub database: String,
+    pub metadata: MetadataNames,
+    pub mappings: Vec<Mapping>,
+}
+
+/// The installations of an instance, and the databases that could not be read.
+#[derive(Debug)]
+pub struct ClusterMappings {
+    pub databases: Vec<DatabaseMappings>,
+    /// Databases that could not be connected to; their mappings are unknown
+    pub unreachable: Vec<String>,
+}
+
+/// Walks every user database of the instance, like `list-mappings`, and returns
+/// those with an installation. Databases that cannot be read are listed in
+/// `unreachable` with a warning, so callers can refuse to act on a partial view.
+pub async fn cluster_mappings(conn_opts: &ConnectionConfig, verbose: u8) -> Result<ClusterMappings> {
+    let mut config = conn_opts.clone();
+    config.dbname = Some("postgres".to_string());
+    let client = connect(&config).await?;
+    let databases = user_databases(&client, verbose).await?;
+    drop(client);
+
+    let mut result = ClusterMappings { databases: Vec::new(), unreachable: Vec::new() };
+    for database in databases {
+        let mut db_config = conn_opts.clone();
+        db_config.dbname = Some(database.clone());
+        let client = match connect(&db_config).await {
+            Ok(client) => client,
+            Err(e) => {
+                // stderr, so a manifest written to stdout by export stays clean
+                eprintln!("Warning: Failed to connect to database '{}': {}", database, e);
+                result.unreachable.push(database);
+                continue;
+            }
+        };
+        let Some(metadata) = discover_metadata(&client, verbose).await? else {
+            continue;
+        };
+        let mappings = load_mappings(&client, &metadata, verbose).await?;
+        result.databases.push(DatabaseMappings { database, metadata, mappings });
+    }
+    Ok(result)
+}
+
+/// Returns every role that is a direct or indirect member of `role`.
+pub async fn role_members(client: &Client, role: &str, verbose: u8) -> Result<Vec<String>> {
+    let sql = "
+        WITH RECURSIVE members AS (
+            SELECT m.member
+            FROM pg_auth_members m
+            JOIN pg_roles g ON g.oid = m.roleid
+            WHERE g.rolname = $1
+            UNION
+            SELECT m.member
+            FROM pg_auth_members m
+            JOIN members ON m.roleid = members.member
+        )
+        SELECT r.rolname::text
+        FROM members
+        JOIN pg_roles r ON r.oid = members.member
+        ORDER BY 1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), role);
+    }
+    let rows = client.query(sql, &[&role])
+        .await
+        .context("Failed to query role members")?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Returns the roles whose default privileges in `schema` grant anything to `grantee`,
+/// along with whether each one is a superuser.
+pub async fn default_privilege_grantors(client: &Client, schema: &str, grantee: &str, verbose: u8) -> Result<Vec<(String, bool)>> {
+    let sql = "
+        SELECT DISTINCT r.rolname::text, r.rolsuper
+        FROM pg_default_acl d
+        JOIN pg_namespace n ON n.oid = d.defaclnamespace
+        JOIN pg_roles r ON r.oid = d.defaclrole
+        CROSS JOIN LATERAL aclexplode(d.defaclacl) a
+        JOIN pg_roles g ON g.oid = a.grantee
+        WHERE n.nspname = $1
+          AND g.rolname = $2
+        ORDER BY 1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, grantee);
+    }
+    let rows = client.query(sql, &[&schema, &grantee])
+        .await
+        .context("Failed to query default privileges")?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Object types (`r`, `S`, `f`) for which default privileges of `grantor` in
+/// `schema` grant anything to `grantee`. `None` means the current user, as for
+/// `ALTER DEFAULT PRIVILEGES` without `FOR ROLE`.
+pub async fn default_privilege_types(client: &Client, grantor: Option<&str>, schema: &str, grantee: &str, verbose: u8) -> Result<Vec<String>> {
+    let sql = "
+        SELECT DISTINCT d.defaclobjtype::text
+        FROM pg_default_acl d
+        JOIN pg_namespace n ON n.oid = d.defaclnamespace
+        CROSS JOIN LATERAL aclexplode(d.defaclacl) a
+        JOIN pg_roles g ON g.oid = a.grantee
+        WHERE d.defaclrole = (SELECT oid FROM pg_roles WHERE rolname = COALESCE($1, current_user))
+          AND n.nspname = $2
+          AND g.rolname = $3
+    ";
+    if verbose >= 1 {
+        println!(
+            "[SQL] {} -- params: [{}, {}, {}]",
+            sql.trim(), grantor.unwrap_or("current_user"), schema, grantee
+        );
+    }
+    let rows = client.query(sql, &[&grantor, &schema, &grantee])
+        .await
+        .context("Failed to query default privileges")?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Whether `grantor` has default privileges in `schema` for `grantee` on tables,
+/// sequences and functions alike, as `alter_default_privileges_for_role` issues them.
+pub async fn has_default_privileges(client: &Client, grantor: &str, schema: &str, grantee: &str, verbose: u8) -> Result<bool> {
+    let types = default_privilege_types(client, Some(grantor), schema, grantee, verbose).await?;
+    Ok(["r", "S", "f"].iter().all(|objtype| types.iter().any(|t| t == objtype)))
+}
+
+/// Roles whose default privileges the tool never revokes: the connected user and
+/// the owner of the config table, which is the role that ran `init`.
+pub async fn protected_grantors(client: &Client, metadata: &MetadataNames, verbose: u8) -> Result<Vec<String>> {
+    let sql = "SELECT current_user::text, pg_get_userbyid(relowner)::text FROM pg_class WHERE oid = $1::text::regclass";
+    let config_table = metadata.qualified_config_table();
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, config_table);
+    }
+    let row = client.query_one(sql, &[&config_table])
+        .await
+        .context("Failed to look up the config table owner")?;
+    Ok(vec![row.get(0), row.get(1)])
+}
+
+/// The grantors among `existing` whose `FOR ROLE` default privileges the tool may
+/// revoke: recorded in the mapping, not protected and not in `keep`. Nothing
+/// while the mapping has no record, since defaults a DBA set up by hand look
+/// the same as the tool's.
+pub fn revocable_grantors(existing: &[(String, bool)], recorded: Option<&[String]>, protected: &[String], keep: &[String]) -> Vec<String> {
+    let Some(recorded) = recorded else {
+        return Vec::new();
+    };
+    existing
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| recorded.contains(name) && !protected.contains(name) && !keep.contains(name))
+        .cloned()
+        .collect()
+}
+
+/// Returns `(pattern, schema)` for every existing schema whose ownership is
+/// currently governed by a pattern mapping, using the trigger's precedence rules.
+/// Only call this on config tables that have the `is_pattern` column.
+pub async fn pattern_matches(client: &Client, metadata: &MetadataNames, verbose: u8) -> Result<Vec<(String, String)>> {
+    let sql = format!(
+        "SELECT pattern, schema
+         FROM (
+             SELECT DISTINCT ON (n.nspname) c.schema_name::text AS pattern, n.nspname::text AS schema, c.is_pattern
+             FROM pg_namespace n
+             JOIN {} c ON {}
+             WHERE n.nspname NOT LIKE 'pg\\_%' AND n.nspname <> 'information_schema'
+             ORDER BY n.nspname, {}
+         ) winners
+         WHERE is_pattern
+         ORDER BY pattern, schema",
+        metadata.qualified_config_table(),
+        mapping_match_condition("n.nspname"),
+        MAPPING_PRECEDENCE
+    );
+    if verbose >= 1 {
+        println!("[SQL] {}", sql);
+    }
+    let rows = client.query(&sql, &[])
+        .await
+        .context("Failed to resolve pattern mappings")?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// A schema whose objects should be owned by `target_role`, either through an
+/// exact mapping or as the winning match of a pattern mapping.
+#[derive(Debug, Clone)]
+pub struct ManagedSchema {
+    pub schema: String,
+    pub target_role: String,
+    pub role_exists: bool,
+}
+
+/// Resolves the active mappings to the schemas they currently govern.
+pub async fn managed_schemas(client: &Client, metadata: &MetadataNames, verbose: u8) -> Result<Vec<ManagedSchema>> {
+    let mappings = load_mappings(client, metadata, verbose).await?;
+
+    let mut managed: Vec<ManagedSchema> = mappings
+        .iter()
+        .filter(|m| m.active && !m.is_pattern)
+        .map(|m| ManagedSchema {
+            schema: m.schema_name.clone(),
+            target_role: m.target_role.clone(),
+            role_exists: m.role_exists,
+        })
+        .collect();
+
+    if mappings.iter().any(|m| m.active && m.is_pattern) {
+        for (pattern, schema) in pattern_matches(client, metadata, verbose).await? {
+            if let Some(m) = mappings.iter().find(|m| m.is_pattern && m.schema_name == pattern) {
+                managed.push(ManagedSchema {
+                    schema,
+                    target_role: m.target_role.clone(),
+                    role_exists: m.role_exists,
+                });
+            }
+        }
+    }
+
+    managed.sort_by(|a, b| a.schema.cmp(&b.schema));
+    Ok(managed)
+}
+
+/// An object that has an owner, as reported by `pg_identify_object`.
+#[derive(Debug, Clone)]
+pub struct OwnedObject {
+    pub schema: String,
+    /// `pg_identify_object` type, e.g. `table`, `function`, `materialized view`
+    pub object_type: String,
+    /// Schema-qualified, quoted identity usable in `ALTER ... OWNER TO`
+    pub identity: String,
+    pub owner: String,
+    /// Sequence owned by a table column (SERIAL/IDENTITY); its owner follows the table
+    pub owned_by_column: bool,
+    /// Bytes on disk including indexes and TOAST, for tables, materialized views
+    /// and sequences; only read with `with_details`
+    pub size: Option<i64>,
+    /// `aclitem` entries, or `None` while the object has its default privileges;
+    /// only read with `with_details`
+    pub acl: Option<Vec<String>>,
+}
+
+/// Lists every owned object in `schemas` (including the schemas themselves),
+/// across all catalogs that have an owner column. Extension members, indexes,
+/// array types and other objects whose owner follows another object are left out.
+/// `with_details` adds sizes and ACLs, which `list-objects` shows; sizing takes
+/// a lock on every relation, so commands that only need owners leave it off.
+pub async fn owned_objects(client: &Client, schemas: &[String], with_details: bool, verbose: u8) -> Result<Vec<OwnedObject>> {
+    let details = if with_details {
+        "CASE WHEN i.type IN ('table', 'materialized view', 'sequence') THEN pg_total_relation_size(o.objid) END,
+               o.acl::text[]"
+    } else {
+        "NULL::bigint, NULL::text[]"
+    };
+    let sql = format!("
+        WITH objects AS (
+            SELECT 'pg_namespace'::regclass AS classid, n.oid AS objid, n.oid AS namespace, n.nspowner AS owner, n.nspacl AS acl
+            FROM pg_namespace n
+            UNION ALL
+            SELECT 'pg_class'::regclass, c.oid, c.relnamespace, c.relowner, c.relacl
+            FROM pg_class c
+            WHERE c.relkind IN ('r', 'p', 'v', 'm', 'S', 'f')
+            UNION ALL
+            SELECT 'pg_proc'::regclass, p.oid, p.pronamespace, p.proowner, p.proacl
+            FROM pg_proc p
+            UNION ALL
+            SELECT 'pg_type'::regclass, t.oid, t.typnamespace, t.typowner, t.typacl
+            FROM pg_type t
+            WHERE (t.typtype IN ('b', 'd', 'e', 'r')
+                   OR (t.typtype = 'c' AND (SELECT c.relkind FROM pg_class c WHERE c.oid = t.typrelid) = 'c'))
+              AND NOT EXISTS (SELECT 1 FROM pg_type e WHERE e.typarray = t.oid)
+            UNION ALL
+            SELECT 'pg_collation'::regclass, oid, collnamespace, collowner, NULL FROM pg_collation
+            UNION ALL
+            SELECT 'pg_conversion'::regclass, oid, connamespace, conowner, NULL FROM pg_conversion
+            UNION ALL
+            SELECT 'pg_operator'::regclass, oid, oprnamespace, oprowner, NULL FROM pg_operator
+            UNION ALL
+            SELECT 'pg_opclass'::regclass, oid, opcnamespace, opcowner, NULL FROM pg_opclass
+            UNION ALL
+            SELECT 'pg_opfamily'::regclass, oid, opfnamespace, opfowner, NULL FROM pg_opfamily
+            UNION ALL
+            SELECT 'pg_ts_config'::regclass, oid, cfgnamespace, cfgowner, NULL FROM pg_ts_config
+            UNION ALL
+            SELECT 'pg_ts_dict'::regclass, oid, dictnamespace, dictowner, NULL FROM pg_ts_dict
+            UNION ALL
+            SELECT 'pg_statistic_ext'::regclass, oid, stxnamespace, stxowner, NULL FROM pg_statistic_ext
+        )
+        SELECT n.nspname::text, i.type, i.identity, pg_get_userbyid(o.owner)::text,
+               o.classid = 'pg_class'::regclass AND EXISTS (
+                   SELECT 1 FROM pg_depend d
+                   WHERE d.classid = 'pg_class'::regclass
+                     AND d.objid = o.objid
+                     AND d.refclassid = 'pg_class'::regclass
+                     AND d.deptype IN ('a', 'i')
+               ),
+               {details}
+        FROM objects o
+        JOIN pg_namespace n ON n.oid = o.namespace
+        CROSS JOIN LATERAL pg_identify_object(o.classid, o.objid, 0) i
+        WHERE n.nspname::text = ANY($1)
+          AND NOT EXISTS (
+              SELECT 1 FROM pg_depend d
+              WHERE d.classid = o.classid
+                AND d.objid = o.objid
+                AND d.deptype = 'e'
+          )
+        ORDER BY 1, o.classid <> 'pg_namespace'::regclass, 2, 3
+    ");
+    if verbose >= 2 {
+        println!("[SQL] {} -- params: [{:?}]", sql.trim(), schemas);
+    }
+    let rows = client.query(&sql, &[&schemas])
+        .await
+        .context("Failed to list owned objects")?;
+
+    Ok(rows
+        .iter()
+        .map(|row| OwnedObject {
+            schema: row.get(0),
+            object_type: row.get(1),
+            identity: row.get(2),
+            owner: row.get(3),
+            owned_by_column: row.get(4),
+            size: row.get(5),
+            acl: row.get(6),
+        })
+        .collect())
+}
@@ -1,4 +1,4 @@
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "pg-app-role-manager")]
@@ -19,11 +19,13 @@ pub struct ConnectionOpts {
     #[arg(long, env = "PGPORT", default_value = "5432")]
     pub port: u16,
 
-    #[arg(long, env = "PGUSER", required = true)]
-    pub user: String,
+    // Required by every command that connects; checked in main so offline
+    // commands run without credentials
+    #[arg(long, env = "PGUSER")]
+    pub user: Option<String>,
 
-    #[arg(long, env = "PGPASSWORD", required = true, hide_env_values = true)]
-    pub password: String,
+    #[arg(long, env = "PGPASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
 
     #[arg(long, env = "PGDATABASE")]
     pub dbname: Option<String>,
@@ -35,18 +37,339 @@ pub struct ConnectionOpts {
     pub verbose: u8,
 }
 
+// Parsed once per run, so the size of the `Init` variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Command {
     Init {
         #[arg(long)]
         database: Option<String>,
 
+        #[arg(long, value_name = "PATTERN", conflicts_with_all = ["database", "database_list"], help = "Run init in every existing database matching a LIKE pattern (e.g. 'cust_%')")]
+        database_pattern: Option<String>,
+
+        #[arg(long, value_name = "FILE", conflicts_with = "database", help = "Run init in every database listed in a file, one per line")]
+        database_list: Option<std::path::PathBuf>,
+
+        #[arg(long, default_value_t = 4, help = "Databases initialized at the same time with --database-pattern or --database-list")]
+        jobs: usize,
+
+        #[arg(long, required_unless_present = "schema_pattern", conflicts_with = "schema_pattern")]
+        schema: Option<String>,
+
+        #[arg(long, help = "Map every schema matching a LIKE pattern (e.g. 'tenant_%'), including ones created later")]
+        schema_pattern: Option<String>,
+
+        #[arg(long, required = true)]
+        role: String,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
+
+        #[arg(long, help = "Move an existing installation to the requested metadata location")]
+        migrate_metadata: bool,
+
+        #[arg(long, help = "Report extensions created in the schema that the target role does not own, with a hint to recreate them [true|false]")]
+        report_extension_owner: Option<bool>,
+
+        #[arg(long, help = "What the event trigger does if the target role is missing: ignore, warn, or error [default for new mappings: warn]")]
+        on_missing_role: Option<String>,
+
+        #[arg(long, conflicts_with = "schema_pattern", help = "Also create companion roles: standard (<role>_migrator, <role>_runtime, <role>_readonly)")]
+        preset: Option<String>,
+
+        #[arg(long, requires = "preset", help = "Name of the preset's migrator role")]
+        migrator_role: Option<String>,
+
+        #[arg(long, requires = "preset", help = "Name of the preset's runtime (read-write) role")]
+        runtime_role: Option<String>,
+
+        #[arg(long, requires = "preset", help = "Name of the preset's read-only role")]
+        readonly_role: Option<String>,
+
+        #[arg(long, help = "Print the SQL init would run, with the outcome of each step, without making changes")]
+        dry_run: bool,
+    },
+    ListMappings {
+        #[arg(long, help = "Also inspect template databases (except template0 and template1)")]
+        include_templates: bool,
+    },
+    /// Install the config table, trigger functions and event triggers in a template database
+    InitTemplate {
+        #[arg(long, required = true, help = "Template database; created and marked IS_TEMPLATE if missing")]
+        template: String,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
+
+        #[arg(long, help = "Move an existing installation to the requested metadata location")]
+        migrate_metadata: bool,
+
+        #[arg(long, help = "Print the plan without making changes")]
+        dry_run: bool,
+    },
+    /// Re-issue default privileges for the current members of each mapped role
+    SyncDefaultPrivileges {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, help = "Only sync this schema's mapping")]
+        schema: Option<String>,
+    },
+    /// Check the installed event triggers, functions, grants and default privileges
+    Doctor {
+        #[arg(long, help = "Only check this database [default: every database with an installation]")]
+        database: Option<String>,
+    },
+    /// Report objects in managed schemas that are not owned by the mapped role
+    Verify {
+        #[arg(long, help = "Only check this database [default: every database with an installation]")]
+        database: Option<String>,
+
+        #[arg(long, help = "Only check this schema")]
+        schema: Option<String>,
+
+        #[arg(long = "type", value_name = "TYPE", help = "Only check objects of this type (table, view, function, ...); repeatable")]
+        object_types: Vec<String>,
+    },
+    /// List every object in the managed schemas with its owner, size and privileges
+    ListObjects {
+        #[arg(long, help = "Only list this database [default: every database with an installation]")]
+        database: Option<String>,
+
+        #[arg(long, help = "Only list this schema")]
+        schema: Option<String>,
+
+        #[arg(long, default_value = "table", help = "Output format: table or json")]
+        format: String,
+    },
+    /// Transfer objects in managed schemas to their mapped role
+    Reconcile {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, help = "Only reconcile this schema")]
+        schema: Option<String>,
+
+        #[arg(long = "type", value_name = "TYPE", help = "Only reconcile objects of this type (table, view, function, ...); repeatable")]
+        object_types: Vec<String>,
+
+        #[arg(long, help = "Print the ownership changes without making them")]
+        dry_run: bool,
+
+        #[arg(long, default_value = "100", help = "Ownership changes per transaction")]
+        batch_size: usize,
+
+        #[arg(long, default_value = "5s", help = "lock_timeout for each batch")]
+        lock_timeout: String,
+    },
+    /// Move a schema and everything in it to a new target role
+    Remap {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true)]
+        schema: String,
+
+        #[arg(long, required = true, help = "Role the schema is mapped to now")]
+        from: String,
+
+        #[arg(long, required = true, help = "New target role (created if missing)")]
+        to: String,
+
+        #[arg(long, help = "Print the plan without making changes")]
+        dry_run: bool,
+
+        #[arg(long, short = 'y', help = "Do not ask for confirmation")]
+        yes: bool,
+    },
+    /// Create a LOGIN role that is a member of a mapping's target role
+    CreateLogin {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true, help = "Name of the new login role")]
+        name: String,
+
+        #[arg(long, required = true, help = "Target role of a mapping in the database")]
+        member_of: String,
+
+        #[arg(long, help = "Make the login's sessions in the database run as the target role (ALTER ROLE ... SET role)")]
+        set_role: bool,
+
+        #[arg(long)]
+        connection_limit: Option<i32>,
+
+        #[arg(long, help = "Password expiry, e.g. '2026-12-31'")]
+        valid_until: Option<String>,
+    },
+    /// Grant a mapping's target role to a user or role, and record it
+    GrantMember {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true, help = "Schema (or pattern) of the mapping whose target role is granted")]
+        schema: String,
+
+        #[arg(long, required = true, help = "User or role to grant the target role to")]
+        member: String,
+
+        #[arg(long, help = "Member inherits the role's privileges (PostgreSQL 16+) [true|false]")]
+        inherit: Option<bool>,
+
+        #[arg(long, help = "Member may SET ROLE to the role (PostgreSQL 16+) [true|false]")]
+        set: Option<bool>,
+
+        #[arg(long, help = "Member may grant the role to others [true|false]")]
+        admin: Option<bool>,
+
+        #[arg(long, help = "Print the plan without making changes")]
+        dry_run: bool,
+    },
+    /// Revoke a mapping's target role from a user or role, and record it
+    RevokeMember {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true, help = "Schema (or pattern) of the mapping whose target role is revoked")]
+        schema: String,
+
         #[arg(long, required = true)]
+        member: String,
+
+        #[arg(long, help = "Print the plan without making changes")]
+        dry_run: bool,
+    },
+    /// Show which login roles can create, own or access objects in a schema, and why
+    WhoCan {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true, help = "Managed schema to report on")]
         schema: String,
+    },
+    /// Run a command (e.g. migrations) whose sessions act as a schema's target role
+    Exec {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, required = true, help = "Managed schema; its target role is set with PGOPTIONS")]
+        schema: String,
+
+        #[arg(last = true, required = true, value_name = "COMMAND", help = "Command and arguments, after '--'")]
+        command: Vec<String>,
+    },
+    /// Converge the mappings on the server with a TOML or YAML manifest
+    Apply {
+        #[arg(long = "file", short = 'f', value_name = "FILE", help = "Manifest file (.toml, .yaml or .yml)")]
+        manifest: std::path::PathBuf,
+
+        #[arg(long, help = "Remove mappings that are on the server but not in the manifest")]
+        prune: bool,
+
+        #[arg(long, help = "Only print the plan; exit nonzero if changes are needed")]
+        check: bool,
+
+        #[arg(long, help = "Move installations to the metadata location given in the manifest, as init --migrate-metadata")]
+        migrate_metadata: bool,
+
+        #[arg(long, short = 'y', help = "Do not ask for confirmation")]
+        yes: bool,
+    },
+    /// Compare the installations of two instances or two databases
+    Diff {
+        #[arg(long, value_name = "CONN", help = "Connection string for the left side, e.g. 'host=staging' or 'postgresql://staging/app'; unset fields come from the connection options")]
+        left: String,
+
+        #[arg(long, value_name = "CONN", help = "Connection string for the right side")]
+        right: String,
+
+        #[arg(long, default_value = "text", help = "Output format: text or json")]
+        format: String,
+    },
+    /// Write every mapping on the server to a manifest
+    Export {
+        #[arg(long, short = 'o', help = "Write the manifest to this file [default: stdout]")]
+        output: Option<std::path::PathBuf>,
+
+        #[arg(long, help = "Manifest format: toml or yaml [default: from the output file extension, else toml]")]
+        format: Option<String>,
+    },
+    /// Run init for every mapping in a manifest written by export
+    Import {
+        #[arg(long = "file", short = 'f', value_name = "FILE", help = "Manifest file (.toml, .yaml or .yml)")]
+        manifest: std::path::PathBuf,
+    },
+    /// Remove mappings, and the event triggers and config table once none remain
+    Uninstall {
+        #[arg(long)]
+        database: Option<String>,
+
+        #[arg(long, help = "Only remove this schema's mapping [default: all mappings]")]
+        schema: Option<String>,
+
+        #[arg(long, help = "Revoke the grants and default privileges init made")]
+        revoke_grants: bool,
+
+        #[arg(long, help = "Drop the mapped schema and everything in it")]
+        drop_schema: bool,
+
+        #[arg(long, help = "Drop the target role if no other mapping uses it (implies --revoke-grants)")]
+        drop_role: bool,
+
+        #[arg(long, short = 'y', help = "Do not ask for confirmation")]
+        yes: bool,
+    },
+    /// Write the SQL init would run as a psql script, without connecting
+    GenerateSql {
+        #[arg(long, required = true)]
+        database: String,
+
+        #[arg(long, required_unless_present = "schema_pattern", conflicts_with = "schema_pattern")]
+        schema: Option<String>,
+
+        #[arg(long, help = "Map every schema matching a LIKE pattern (e.g. 'tenant_%'), including ones created later")]
+        schema_pattern: Option<String>,
 
         #[arg(long, required = true)]
         role: String,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
+
+        #[arg(long, help = "Report extensions created in the schema that the target role does not own, with a hint to recreate them [true|false]")]
+        report_extension_owner: Option<bool>,
+
+        #[arg(long, help = "What the event trigger does if the target role is missing: ignore, warn, or error [default for new mappings: warn]")]
+        on_missing_role: Option<String>,
+
+        #[arg(long, default_value = "15", help = "Major version of the server the script is for")]
+        server_version: i32,
+
+        #[arg(long, short = 'o', help = "Write the script to this file [default: stdout]")]
+        output: Option<std::path::PathBuf>,
     },
-    ListMappings,
     Version,
 }
+
+/// Where `init` and `generate-sql` put the config table, functions and event triggers.
+#[derive(Args)]
+pub struct MetadataArgs {
+    #[arg(long, help = "Schema holding the config table and trigger function [default: existing location, else public]")]
+    pub metadata_schema: Option<String>,
+
+    #[arg(long, help = "Config table name [default: schema_ownership_config]")]
+    pub config_table: Option<String>,
+
+    #[arg(long, help = "Trigger function name [default: auto_transfer_schema_ownership]")]
+    pub function_name: Option<String>,
+
+    #[arg(long, help = "Event trigger name [default: auto_transfer_schema_ownership_trigger]")]
+    pub trigger_name: Option<String>,
+
+    #[arg(long, help = "Dropped-schema cleanup function name [default: schema_ownership_drop_cleanup]")]
+    pub cleanup_function_name: Option<String>,
+
+    #[arg(long, help = "Dropped-schema cleanup event trigger name [default: schema_ownership_drop_cleanup_trigger]")]
+    pub cleanup_trigger_name: Option<String>,
+}
@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::catalog::{cluster_mappings, Mapping};
+use crate::commands::init::InitOptions;
+use crate::commands::uninstall::UninstallOptions;
+use crate::db::ConnectionConfig;
+use crate::manifest;
+use crate::prompt::confirm;
+use crate::report::{ActionOutcome, Plan};
+use crate::sql_templates::{MappingOptions, MetadataNames, PresetRoles};
+
+/// How `apply` treats the manifest.
+#[derive(Debug)]
+pub struct ApplyOptions {
+    pub manifest: PathBuf,
+    /// Remove mappings that are on the server but not in the manifest
+    pub prune: bool,
+    /// Only report whether changes are needed; exits 1 if they are
+    pub check: bool,
+    /// Move installations whose metadata location differs from the manifest's
+    pub migrate_metadata: bool,
+    pub yes: bool,
+}
+
+/// A change `apply` makes to converge on the manifest.
+enum Change {
+    Init(Box<InitOptions>),
+    Prune { database: String, schema: String },
+}
+
+/// Brings the mappings on the server in line with a manifest: missing mappings
+/// are initialized, changed options updated, and with `--prune` mappings not in
+/// the manifest removed. Returns `false` when `--check` finds the server out of
+/// line with the manifest.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ApplyOptions, verbose: u8) -> Result<bool> {
+    let manifest = manifest::load(&opts.manifest)?;
+
+    // Live mappings, found the way list-mappings finds them
+    let cluster = cluster_mappings(&conn_opts, verbose).await?;
+    // Without its current mappings a database would be planned as if it had none
+    let unreadable: Vec<&str> = manifest.databases
+        .iter()
+        .map(|d| d.name.as_str())
+        .filter(|name| cluster.unreachable.iter().any(|u| u == name))
+        .collect();
+    if !unreadable.is_empty() {
+        anyhow::bail!(
+            "Could not read database(s) named in {}: {}. Nothing was planned or changed.",
+            opts.manifest.display(),
+            unreadable.join(", ")
+        );
+    }
+    let installations: Vec<(String, MetadataNames)> = cluster.databases
+        .iter()
+        .map(|db| (db.database.clone(), db.metadata.clone()))
+        .collect();
+    let live: Vec<(String, Mapping)> = cluster.databases
+        .into_iter()
+        .flat_map(|db| db.mappings.into_iter().map(move |m| (db.database.clone(), m)))
+        .collect();
+
+    let mut plan = Plan::new(if opts.check { "Apply Plan (check)" } else { "Apply Plan" });
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    // Databases whose installation is not where the manifest puts it
+    let mut relocations = Vec::new();
+
+    for database in &manifest.databases {
+        let installed = installations.iter().find(|(db, _)| *db == database.name).map(|(_, m)| m);
+        if let Some(installed) = installed {
+            let wanted_metadata = database.metadata_options().resolve(installed);
+            let differences = metadata_differences(installed, &wanted_metadata);
+            if !differences.is_empty() {
+                let description = format!("{}: installation ({})", database.name, differences.join(", "));
+                // init moves the installation; any mapping the server agrees on can carry the move
+                let carrier = database.mappings.iter().find(|wanted| {
+                    let (schema, is_pattern) = wanted.schema_name();
+                    live.iter()
+                        .find(|(db, m)| *db == database.name && m.schema_name == schema)
+                        .is_none_or(|(_, m)| m.is_pattern == is_pattern && m.target_role == wanted.role)
+                });
+                match carrier {
+                    Some(wanted) if opts.migrate_metadata => {
+                        plan.add(description, ActionOutcome::Updated, Vec::new());
+                        let (schema, is_pattern) = wanted.schema_name();
+                        changes.push(Change::Init(Box::new(InitOptions {
+                            database: database.name.clone(),
+                            schema: schema.to_string(),
+                            schema_is_pattern: is_pattern,
+                            role: wanted.role.clone(),
+                            metadata: database.metadata_options(),
+                            migrate_metadata: true,
+                            mapping: wanted.options()?,
+                            preset: wanted.preset_roles()?,
+                            dry_run: false,
+                        })));
+                    }
+                    Some(_) => {
+                        plan.add(format!("{}; --migrate-metadata moves it", description), ActionOutcome::Updated, Vec::new());
+                        relocations.push(database.name.clone());
+                    }
+                    None => conflicts.push(format!(
+                        "{}: no mapping in the manifest that the server agrees on, to move it with",
+                        description
+                    )),
+                }
+            }
+        }
+
+        for wanted in &database.mappings {
+            let (schema, is_pattern) = wanted.schema_name();
+            let options = wanted.options()?;
+            let preset = wanted.preset_roles()?;
+            let label = format!("{}.{} -> {}", database.name, schema, wanted.role);
+            let current = live
+                .iter()
+                .find(|(db, m)| *db == database.name && m.schema_name == schema)
+                .map(|(_, m)| m);
+
+            let outcome = match current {
+                None => Some((ActionOutcome::Created, label.clone())),
+                Some(m) if m.is_pattern != is_pattern => {
+                    conflicts.push(format!(
+                        "{}: mapped as a {} on the server",
+                        label,
+                        if m.is_pattern { "pattern" } else { "single schema" }
+                    ));
+                    None
+                }
+                // Inactive mappings are taken over by init
+                Some(m) if !m.active => Some((ActionOutcome::Updated, format!("{} (reactivate)", label))),
+                Some(m) if m.target_role != wanted.role => {
+                    conflicts.push(format!(
+                        "{}: mapped to '{}' on the server; use 'remap --database {} --schema {} --from {} --to {}'",
+                        label, m.target_role, database.name, schema, m.target_role, wanted.role
+                    ));
+                    None
+                }
+                Some(m) if !m.role_exists => Some((ActionOutcome::Updated, format!("{} (create missing role)", label))),
+                Some(m) => {
+                    let differences = option_differences(&options, preset.as_ref(), m);
+                    if differences.is_empty() {
+                        plan.add(label.clone(), ActionOutcome::Skipped, Vec::new());
+                        None
+                    } else {
+                        Some((ActionOutcome::Updated, format!("{} ({})", label, differences.join(", "))))
+                    }
+                }
+            };
+
+            if let Some((outcome, description)) = outcome {
+                plan.add(description, outcome, Vec::new());
+                changes.push(Change::Init(Box::new(InitOptions {
+                    database: database.name.clone(),
+                    schema: schema.to_string(),
+                    schema_is_pattern: is_pattern,
+                    role: wanted.role.clone(),
+                    metadata: database.metadata_options(),
+                    migrate_metadata: false,
+                    mapping: options,
+                    preset,
+                    dry_run: false,
+                })));
+            }
+        }
+    }
+
+    let mut unmanaged = 0;
+    for (database, mapping) in &live {
+        let in_manifest = manifest.databases.iter().any(|d| {
+            d.name == *database && d.mappings.iter().any(|m| m.schema_name().0 == mapping.schema_name)
+        });
+        if in_manifest {
+            continue;
+        }
+        let label = format!("{}.{} -> {}", database, mapping.schema_name, mapping.target_role);
+        if opts.prune {
+            plan.add(format!("{} (not in manifest)", label), ActionOutcome::Removed, Vec::new());
+            changes.push(Change::Prune { database: database.clone(), schema: mapping.schema_name.clone() });
+        } else {
+            plan.add(format!("{} (not in manifest; --prune removes it)", label), ActionOutcome::Skipped, Vec::new());
+            unmanaged += 1;
+        }
+    }
+
+    plan.print(verbose);
+    for conflict in &conflicts {
+        println!("Conflict: {}", conflict);
+    }
+    if !conflicts.is_empty() {
+        println!();
+    }
+    if unmanaged > 0 {
+        println!("{} mapping(s) on the server are not in the manifest.", unmanaged);
+    }
+
+    if opts.check {
+        if changes.is_empty() && relocations.is_empty() && conflicts.is_empty() {
+            println!("No changes needed.");
+            return Ok(true);
+        }
+        println!(
+            "{} change(s) needed and {} conflict(s) with {}",
+            changes.len() + relocations.len(), conflicts.len(), opts.manifest.display()
+        );
+        return Ok(false);
+    }
+
+    // init refuses to run against an installation elsewhere, so stop before changing anything
+    if !relocations.is_empty() {
+        anyhow::bail!(
+            "The installation in {} is not at the metadata location in {}. \
+             Re-run with --migrate-metadata to move it, or change the manifest. Nothing was changed.",
+            relocations.join(", "),
+            opts.manifest.display()
+        );
+    }
+
+    if changes.is_empty() {
+        println!("No changes needed.");
+    }
+
+    let prunes = changes.iter().filter(|c| matches!(c, Change::Prune { .. })).count();
+    if prunes > 0 && !confirm(&format!("Remove {} mapping(s) not in the manifest?", prunes), opts.yes)? {
+        println!("Aborted.");
+        return Ok(true);
+    }
+
+    for change in changes {
+        println!();
+        match change {
+            Change::Init(init_opts) => {
+                println!("==> init {}.{} -> {}", init_opts.database, init_opts.schema, init_opts.role);
+                crate::commands::init::execute(conn_opts.clone(), *init_opts, verbose).await?;
+            }
+            Change::Prune { database, schema } => {
+                println!("==> uninstall {}.{}", database, schema);
+                let uninstall_opts = UninstallOptions {
+                    database,
+                    schema: Some(schema),
+                    revoke_grants: false,
+                    drop_schema: false,
+                    drop_role: false,
+                    yes: true,
+                };
+                crate::commands::uninstall::execute(conn_opts.clone(), uninstall_opts, verbose).await?;
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        anyhow::bail!("{} mapping(s) conflict with the manifest and were left unchanged", conflicts.len());
+    }
+
+    Ok(true)
+}
+
+/// Names of the installation that the manifest places elsewhere.
+fn metadata_differences(installed: &MetadataNames, wanted: &MetadataNames) -> Vec<String> {
+    [
+        ("schema", &installed.schema, &wanted.schema),
+        ("config_table", &installed.config_table, &wanted.config_table),
+        ("function", &installed.function, &wanted.function),
+        ("event_trigger", &installed.event_trigger, &wanted.event_trigger),
+        ("cleanup_function", &installed.cleanup_function, &wanted.cleanup_function),
+        ("cleanup_event_trigger", &installed.cleanup_event_trigger, &wanted.cleanup_event_trigger),
+    ]
+    .into_iter()
+    .filter(|(_, current, wanted)| current != wanted)
+    .map(|(name, current, wanted)| format!("{} {} -> {}", name, current, wanted))
+    .collect()
+}
+
+/// Options set in the manifest that differ from the server; unset ones are left alone.
+fn option_differences(options: &MappingOptions, preset: Option<&PresetRoles>, current: &Mapping) -> Vec<String> {
+    let mut differences = Vec::new();
+    if let Some(report) = options.report_extension_owner
+        && report != current.report_extension_owner
+    {
+        differences.push(format!("report_extension_owner {} -> {}", current.report_extension_owner, report));
+    }
+    if let Some(policy) = options.on_missing_role
+        && policy.as_str() != current.on_missing_role
+    {
+        differences.push(format!("on_missing_role {} -> {}", current.on_missing_role, policy.as_str()));
+    }
+    if let Some(preset) = preset
+        && current.preset.as_ref() != Some(preset)
+    {
+        differences.push(format!(
+            "preset roles {} -> {}, {}, {}",
+            current.preset.as_ref().map_or("none".to_string(), |p| format!("{}, {}, {}", p.migrator, p.runtime, p.readonly)),
+            preset.migrator, preset.runtime, preset.readonly
+        ));
+    }
+    differences
+}
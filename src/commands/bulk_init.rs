@@ -0,0 +1,226 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::catalog::{cluster_mappings, BLOCKED_DATABASES};
+use crate::db::{connect, ConnectionConfig};
+use crate::report::{ActionOutcome, ActionReport};
+
+/// Flags that select the databases of a bulk run; they are not passed on to
+/// the per-database `init` runs.
+const BULK_FLAGS: [&str; 3] = ["--database-pattern", "--database-list", "--jobs"];
+
+/// Which databases a bulk `init` covers.
+#[derive(Debug)]
+pub enum DatabaseSelection {
+    /// LIKE pattern over the existing non-template databases
+    Pattern(String),
+    /// File with one database name per line; `#` starts a comment
+    List(PathBuf),
+}
+
+/// How `init --database-pattern` / `--database-list` runs.
+#[derive(Debug)]
+pub struct BulkInitOptions {
+    pub selection: DatabaseSelection,
+    /// Schema name, or LIKE pattern with `--schema-pattern`, the runs map
+    pub schema: String,
+    /// Databases initialized at the same time
+    pub jobs: usize,
+    /// The runs only print their plans
+    pub dry_run: bool,
+}
+
+/// Runs `init` with the same options in every selected database. The first
+/// database runs alone, so roles shared by all of them are created once; the
+/// rest run concurrently, each in its own process with its output buffered.
+/// A failure does not stop the other databases; the command fails at the end
+/// if any did.
+pub async fn execute(conn_opts: ConnectionConfig, opts: BulkInitOptions, verbose: u8) -> Result<()> {
+    let databases = match &opts.selection {
+        DatabaseSelection::Pattern(pattern) => matching_databases(&conn_opts, pattern, verbose).await?,
+        DatabaseSelection::List(path) => read_database_list(path)?,
+    };
+    if databases.is_empty() {
+        anyhow::bail!("No databases selected; nothing to do");
+    }
+    if let Some(blocked) = databases.iter().find(|d| BLOCKED_DATABASES.contains(&d.as_str())) {
+        anyhow::bail!("'{}' is a system database and cannot be initialized", blocked);
+    }
+
+    // Taken before any run starts, so each database's outcome is known without
+    // reading the child's output
+    let live = cluster_mappings(&conn_opts, verbose).await?.databases;
+    let mapped: Vec<&str> = live
+        .iter()
+        .filter(|db| db.mappings.iter().any(|m| m.schema_name == opts.schema))
+        .map(|db| db.database.as_str())
+        .collect();
+
+    let program = std::env::current_exe().context("Failed to locate the pg-app-role-manager executable")?;
+    let args = Arc::new(child_args(std::env::args_os().skip(1)));
+    println!("Running init in {} database(s), {} at a time.", databases.len(), opts.jobs);
+    println!();
+
+    let mut report = ActionReport::new(if opts.dry_run { "Bulk Init (dry run)" } else { "Bulk Init" });
+    let mut failed = Vec::new();
+    let mut record = |database: String, result: Result<(bool, Vec<u8>)>| {
+        println!("==> init {}", database);
+        let outcome = match result {
+            Ok((true, output)) => {
+                print!("{}", String::from_utf8_lossy(&output));
+                if mapped.contains(&database.as_str()) {
+                    ActionOutcome::Updated
+                } else {
+                    ActionOutcome::Created
+                }
+            }
+            Ok((false, output)) => {
+                print!("{}", String::from_utf8_lossy(&output));
+                failed.push(database.clone());
+                ActionOutcome::Failed
+            }
+            Err(e) => {
+                println!("Error: {:#}", e);
+                failed.push(database.clone());
+                ActionOutcome::Failed
+            }
+        };
+        println!();
+        report.record(format!("Database '{}'", database), outcome);
+        println!();
+    };
+
+    let (first, rest) = databases.split_first().expect("checked above");
+    let result = run_init(&program, &args, first).await;
+    record(first.clone(), result);
+
+    let semaphore = Arc::new(Semaphore::new(opts.jobs.max(1)));
+    let mut tasks = JoinSet::new();
+    for database in rest.iter().cloned() {
+        let semaphore = semaphore.clone();
+        let program = program.clone();
+        let args = args.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("semaphore is never closed");
+            let result = run_init(&program, &args, &database).await;
+            (database, result)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        let (database, result) = joined.context("init task panicked")?;
+        record(database, result);
+    }
+
+    report.print_summary();
+    if !failed.is_empty() {
+        println!();
+        println!("Failed databases: {}", failed.join(", "));
+        anyhow::bail!("init failed in {} of {} database(s)", failed.len(), databases.len());
+    }
+    Ok(())
+}
+
+/// Runs `init` for one database as a child process and returns whether it
+/// succeeded, with its stdout and stderr combined.
+async fn run_init(program: &Path, args: &[OsString], database: &str) -> Result<(bool, Vec<u8>)> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .arg("--database")
+        .arg(database)
+        .output()
+        .await
+        .with_context(|| format!("Failed to start init for database '{}'", database))?;
+    let mut combined = output.stdout;
+    combined.extend(output.stderr);
+    Ok((output.status.success(), combined))
+}
+
+/// The command line of this run without the bulk flags, to which each child
+/// adds its own `--database`. Connection flags and `init` options carry over.
+fn child_args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut result = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        let text = arg.to_string_lossy();
+        if BULK_FLAGS.contains(&text.as_ref()) {
+            skip_value = true;
+            continue;
+        }
+        if BULK_FLAGS.iter().any(|flag| text.starts_with(&format!("{}=", flag))) {
+            continue;
+        }
+        result.push(arg);
+    }
+    result
+}
+
+async fn matching_databases(conn_opts: &ConnectionConfig, pattern: &str, verbose: u8) -> Result<Vec<String>> {
+    let mut config = conn_opts.clone();
+    config.dbname = Some("postgres".to_string());
+    let client = connect(&config).await?;
+
+    let sql = "SELECT datname FROM pg_database WHERE datistemplate = false AND datname LIKE $1 ORDER BY datname";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, pattern);
+    }
+    let rows = client.query(sql, &[&pattern])
+        .await
+        .context("Failed to query pg_database")?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get(0))
+        .filter(|dbname: &String| !BLOCKED_DATABASES.contains(&dbname.as_str()))
+        .collect())
+}
+
+fn read_database_list(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut databases: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let name = line.split('#').next().unwrap_or_default().trim();
+        if !name.is_empty() && !databases.iter().any(|d| d == name) {
+            databases.push(name.to_string());
+        }
+    }
+    Ok(databases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_args_drop_bulk_flags_and_their_values() {
+        let args = [
+            "--host", "db1", "init", "--database-pattern", "cust_%", "--schema", "app",
+            "--jobs=8", "--role", "app_owner", "--database-list", "dbs.txt", "--dry-run",
+        ];
+        let result = child_args(args.iter().map(OsString::from));
+        let expected: Vec<OsString> = ["--host", "db1", "init", "--schema", "app", "--role", "app_owner", "--dry-run"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn database_list_skips_comments_blanks_and_duplicates() {
+        let path = std::env::temp_dir().join(format!("pgarm-database-list-{}.txt", std::process::id()));
+        std::fs::write(&path, "# customers\ncust_a\n\n  cust_b  # moved in March\ncust_a\n").unwrap();
+        let databases = read_database_list(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(databases.unwrap(), vec!["cust_a".to_string(), "cust_b".to_string()]);
+
+        assert!(read_database_list(Path::new("/nonexistent/databases.txt")).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{discover_metadata, load_mappings};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::prompt::new_password;
+use crate::report::{ActionOutcome, ActionReport};
+use crate::sql_templates::{MembershipOptions, SqlTemplates};
+
+/// The login `create-login` adds next to a mapping's target role.
+#[derive(Debug)]
+pub struct CreateLoginOptions {
+    pub database: String,
+    pub name: String,
+    /// Target role of a mapping in `database`
+    pub member_of: String,
+    /// Run the login's sessions in `database` as the target role
+    pub set_role: bool,
+    pub connection_limit: Option<i32>,
+    pub valid_until: Option<String>,
+}
+
+/// Creates a LOGIN role that is a member of a mapping's target role. The
+/// password is hashed to a SCRAM-SHA-256 verifier before it is sent.
+pub async fn execute(conn_opts: ConnectionConfig, opts: CreateLoginOptions, verbose: u8) -> Result<()> {
+    let mut report = ActionReport::new("Create Login");
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let mut client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+    let mappings: Vec<_> = load_mappings(&client, &metadata, verbose).await?
+        .into_iter()
+        .filter(|m| m.active && m.target_role == opts.member_of)
+        .collect();
+    let Some(mapping) = mappings.first() else {
+        anyhow::bail!(
+            "'{}' is not the target role of an active mapping in database '{}' (see 'list-mappings')",
+            opts.member_of, opts.database
+        );
+    };
+    if !mapping.role_exists {
+        anyhow::bail!("Target role '{}' does not exist", opts.member_of);
+    }
+
+    let sql = "SELECT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = $1)";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, opts.name);
+    }
+    let exists: bool = client.query_one(sql, &[&opts.name])
+        .await
+        .context("Failed to look up role")?
+        .get(0);
+    if exists {
+        anyhow::bail!("Role '{}' already exists", opts.name);
+    }
+
+    let password = new_password(&opts.name)?;
+    let verifier = postgres_protocol::password::scram_sha_256(password.as_bytes());
+
+    let templates = SqlTemplates::new(
+        opts.database.clone(),
+        mapping.schema_name.clone(),
+        opts.member_of.clone(),
+        metadata,
+        capabilities,
+    );
+
+    let transaction = client.transaction().await
+        .context("Failed to start transaction")?;
+    let execute = async |sql: &str, logged: &str| -> Result<()> {
+        if verbose >= 1 {
+            println!("[SQL] {}", logged);
+        }
+        transaction.batch_execute(sql).await
+            .with_context(|| format!("Failed: {}", logged))?;
+        Ok(())
+    };
+
+    let valid_until = opts.valid_until.as_deref();
+    let sql = templates.create_login(&opts.name, &verifier, opts.connection_limit, valid_until);
+    // The verifier is not the password, but it allows offline guessing
+    let logged = templates.create_login(&opts.name, "SCRAM-SHA-256$...", opts.connection_limit, valid_until);
+    execute(&sql, &logged).await?;
+    report.record(format!("Login role '{}'", opts.name), ActionOutcome::Created);
+
+    for sql in templates.grant_membership(&opts.name, &MembershipOptions::default()) {
+        execute(&sql, &sql).await?;
+    }
+    for sql in [
+        templates.create_history_table(),
+        templates.record_membership("create-login", &opts.name, ""),
+    ] {
+        execute(&sql, &sql).await?;
+    }
+    report.record(format!("Membership of '{}' in '{}'", opts.name, opts.member_of), ActionOutcome::Created);
+
+    if opts.set_role {
+        let sql = templates.set_default_role(&opts.name);
+        execute(&sql, &sql).await?;
+        report.record(
+            format!("Sessions of '{}' in '{}' run as '{}'", opts.name, opts.database, opts.member_of),
+            ActionOutcome::Created,
+        );
+    }
+
+    // Objects the login creates without switching role get the role's default grants
+    for mapping in mappings.iter().filter(|m| !m.is_pattern) {
+        let schema_templates = SqlTemplates::new(
+            opts.database.clone(),
+            mapping.schema_name.clone(),
+            opts.member_of.clone(),
+            templates.metadata.clone(),
+            capabilities,
+        );
+        for sql in schema_templates.alter_default_privileges_for_role(&opts.name) {
+            execute(&sql, &sql).await?;
+        }
+        for sql in schema_templates.record_default_privilege_grantors(std::slice::from_ref(&opts.name)) {
+            execute(&sql, &sql).await?;
+        }
+        report.record(
+            format!("Default privileges for role '{}' in schema '{}'", opts.name, mapping.schema_name),
+            ActionOutcome::Created,
+        );
+    }
+
+    transaction.commit().await
+        .context("Failed to commit")?;
+
+    report.print_summary();
+    Ok(())
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio_postgres::Client;
+
+use crate::catalog::{discover_metadata, load_mappings, user_databases};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::sql_templates::SqlTemplates;
+
+/// The two sides `diff` compares, as libpq connection strings.
+#[derive(Debug)]
+pub struct DiffOptions {
+    pub left: String,
+    pub right: String,
+    pub json: bool,
+}
+
+/// What was found on one side: `(scope, item) -> value`. An item missing on
+/// one side is a difference like any other.
+type Facts = BTreeMap<(String, String), String>;
+
+/// One item that differs; `None` means it is missing on that side.
+#[derive(Debug, Serialize)]
+struct Difference {
+    scope: String,
+    item: String,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    left: String,
+    right: String,
+    differences: Vec<Difference>,
+}
+
+/// Compares the installations of two instances, or of two databases when both
+/// connection strings name one: mappings, the installed functions and event
+/// triggers, the target roles and the grants on managed schemas. Returns
+/// whether the two sides match.
+pub async fn execute(conn_opts: ConnectionConfig, opts: DiffOptions, verbose: u8) -> Result<bool> {
+    // PGDATABASE must not turn a cluster comparison into a database comparison
+    let base = ConnectionConfig { dbname: None, ..conn_opts };
+    let left = base.with_conninfo(&opts.left)?;
+    let right = base.with_conninfo(&opts.right)?;
+
+    let database_mode = match (&left.dbname, &right.dbname) {
+        (Some(_), Some(_)) => true,
+        (None, None) => false,
+        _ => anyhow::bail!("Name a database on both sides (to compare two databases) or on neither (to compare two instances)"),
+    };
+
+    let (mut left_facts, mut left_roles) = collect_databases(&left, database_mode, verbose).await
+        .with_context(|| format!("Failed to read {}", left.display_target()))?;
+    let (mut right_facts, right_roles) = collect_databases(&right, database_mode, verbose).await
+        .with_context(|| format!("Failed to read {}", right.display_target()))?;
+
+    // Both sides describe the same roles, so one missing on a side shows up
+    left_roles.extend(right_roles);
+    collect_roles(&left, &left_roles, &mut left_facts, verbose).await?;
+    collect_roles(&right, &left_roles, &mut right_facts, verbose).await?;
+
+    let keys: BTreeSet<&(String, String)> = left_facts.keys().chain(right_facts.keys()).collect();
+    let differences: Vec<Difference> = keys
+        .into_iter()
+        .filter(|key| left_facts.get(*key) != right_facts.get(*key))
+        .map(|key| Difference {
+            scope: key.0.clone(),
+            item: key.1.clone(),
+            left: left_facts.get(key).cloned(),
+            right: right_facts.get(key).cloned(),
+        })
+        .collect();
+
+    let report = DiffReport {
+        left: left.display_target(),
+        right: right.display_target(),
+        differences,
+    };
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report).context("Failed to render JSON")?);
+    } else {
+        print_report(&report);
+    }
+
+    Ok(report.differences.is_empty())
+}
+
+fn print_report(report: &DiffReport) {
+    println!("Comparing {} (left) with {} (right)", report.left, report.right);
+    let mut scope = None;
+    for difference in &report.differences {
+        if scope != Some(&difference.scope) {
+            println!();
+            println!("{}", difference.scope);
+            scope = Some(&difference.scope);
+        }
+        println!("  {}", difference.item);
+        println!("    left:  {}", difference.left.as_deref().unwrap_or("(missing)"));
+        println!("    right: {}", difference.right.as_deref().unwrap_or("(missing)"));
+    }
+    println!();
+    if report.differences.is_empty() {
+        println!("No differences.");
+    } else {
+        println!("{} difference(s).", report.differences.len());
+    }
+}
+
+/// Facts about every installation on one side, and the target roles it maps to.
+async fn collect_databases(config: &ConnectionConfig, database_mode: bool, verbose: u8) -> Result<(Facts, BTreeSet<String>)> {
+    let databases = if database_mode {
+        vec![config.dbname.clone().unwrap_or_default()]
+    } else {
+        let mut postgres = config.clone();
+        postgres.dbname = Some("postgres".to_string());
+        user_databases(&connect(&postgres).await?, verbose).await?
+    };
+
+    let mut facts = Facts::new();
+    let mut roles = BTreeSet::new();
+    for database in databases {
+        let mut db_config = config.clone();
+        db_config.dbname = Some(database.clone());
+        let client = connect(&db_config).await?;
+
+        // Two databases compared directly are one scope, whatever their names
+        let scope = if database_mode { "database".to_string() } else { format!("database '{}'", database) };
+        let mut fact = |item: String, value: String| {
+            facts.insert((scope.clone(), item), value);
+        };
+
+        let Some(metadata) = discover_metadata(&client, verbose).await? else {
+            // Unmanaged databases only matter when compared directly
+            if database_mode {
+                fact("installation".to_string(), "none".to_string());
+            }
+            continue;
+        };
+        fact("installation".to_string(), metadata.qualified_config_table());
+
+        let capabilities = server_capabilities(&client).await?;
+        let templates = SqlTemplates::new(database.clone(), String::new(), String::new(), metadata.clone(), capabilities);
+        for (label, name, expected) in [
+            ("trigger function", &metadata.function, templates.create_trigger_function()),
+            ("cleanup function", &metadata.cleanup_function, templates.create_cleanup_function()),
+        ] {
+            if let Some(value) = function_state(&client, &metadata.schema, name, &expected, verbose).await? {
+                fact(label.to_string(), value);
+            }
+        }
+        for (label, name) in [
+            ("event trigger (ddl_command_end)", &metadata.event_trigger),
+            ("event trigger (sql_drop)", &metadata.cleanup_event_trigger),
+        ] {
+            if let Some(state) = event_trigger_state(&client, name, verbose).await? {
+                fact(label.to_string(), format!("{}: {}", name, state));
+            }
+        }
+
+        for mapping in load_mappings(&client, &metadata, verbose).await? {
+            let mut value = format!(
+                "-> {} (on_missing_role={}, report_extension_owner={})",
+                mapping.target_role, mapping.on_missing_role, mapping.report_extension_owner
+            );
+            if mapping.is_pattern {
+                value.push_str(" [pattern]");
+            }
+            if !mapping.active {
+                value.push_str(" [inactive]");
+            }
+            fact(format!("mapping '{}'", mapping.schema_name), value);
+            roles.insert(mapping.target_role.clone());
+
+            if mapping.active && !mapping.is_pattern {
+                for (item, value) in schema_grants(&client, &mapping.schema_name, verbose).await? {
+                    fact(format!("schema '{}' {}", mapping.schema_name, item), value);
+                }
+            }
+        }
+    }
+    Ok((facts, roles))
+}
+
+/// Whether the function matches what this version of the tool installs, with a
+/// short fingerprint of its source.
+async fn function_state(client: &Client, schema: &str, name: &str, expected_sql: &str, verbose: u8) -> Result<Option<String>> {
+    let sql = "
+        SELECT p.prosrc, left(md5(p.prosrc), 12)
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE n.nspname = $1
+          AND p.proname = $2
+          AND p.pronargs = 0
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, name);
+    }
+    let row = client.query_opt(sql, &[&schema, &name])
+        .await
+        .context("Failed to look up function")?;
+    Ok(row.map(|row| {
+        let source: String = row.get(0);
+        let fingerprint: String = row.get(1);
+        // The stored source is the text between the dollar quotes
+        let expected = expected_sql.split("$$").nth(1).unwrap_or_default();
+        if source == expected {
+            format!("version {} (md5 {})", env!("CARGO_PKG_VERSION"), fingerprint)
+        } else {
+            format!("edited or from another version (md5 {})", fingerprint)
+        }
+    }))
+}
+
+async fn event_trigger_state(client: &Client, name: &str, verbose: u8) -> Result<Option<String>> {
+    let sql = "SELECT evtenabled::text FROM pg_event_trigger WHERE evtname = $1";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, name);
+    }
+    let row = client.query_opt(sql, &[&name])
+        .await
+        .context("Failed to look up event trigger")?;
+    // evtenabled: O = origin and local (the default), R = replica only, A = always, D = disabled
+    Ok(row.map(|row| {
+        let enabled: String = row.get(0);
+        match enabled.as_str() {
+            "O" => "enabled",
+            "R" => "replica only",
+            "A" => "always",
+            "D" => "disabled",
+            _ => "unknown",
+        }
+        .to_string()
+    }))
+}
+
+/// Owner of the schema and the privileges each role holds on it.
+async fn schema_grants(client: &Client, schema: &str, verbose: u8) -> Result<Vec<(String, String)>> {
+    let sql = "
+        SELECT 'grants to ' || CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE quote_ident(pg_get_userbyid(a.grantee)) END,
+               string_agg(a.privilege_type, ', ' ORDER BY a.privilege_type)
+        FROM pg_namespace n
+        CROSS JOIN LATERAL aclexplode(COALESCE(n.nspacl, acldefault('n', n.nspowner))) a
+        WHERE n.nspname = $1
+        GROUP BY a.grantee
+        UNION ALL
+        SELECT 'owner', pg_get_userbyid(n.nspowner)::text
+        FROM pg_namespace n
+        WHERE n.nspname = $1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), schema);
+    }
+    let rows = client.query(sql, &[&schema])
+        .await
+        .context("Failed to query schema privileges")?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Attributes and direct memberships of each role, which are shared by every
+/// database of an instance.
+async fn collect_roles(config: &ConnectionConfig, roles: &BTreeSet<String>, facts: &mut Facts, verbose: u8) -> Result<()> {
+    let mut role_config = config.clone();
+    role_config.dbname.get_or_insert_with(|| "postgres".to_string());
+    let client = connect(&role_config).await?;
+
+    let sql = "
+        SELECT concat_ws(' ',
+                   CASE WHEN r.rolsuper THEN 'SUPERUSER' ELSE 'NOSUPERUSER' END,
+                   CASE WHEN r.rolinherit THEN 'INHERIT' ELSE 'NOINHERIT' END,
+                   CASE WHEN r.rolcreaterole THEN 'CREATEROLE' ELSE 'NOCREATEROLE' END,
+                   CASE WHEN r.rolcreatedb THEN 'CREATEDB' ELSE 'NOCREATEDB' END,
+                   CASE WHEN r.rolcanlogin THEN 'LOGIN' ELSE 'NOLOGIN' END,
+                   CASE WHEN r.rolreplication THEN 'REPLICATION' ELSE 'NOREPLICATION' END,
+                   CASE WHEN r.rolbypassrls THEN 'BYPASSRLS' ELSE 'NOBYPASSRLS' END,
+                   'CONNECTION LIMIT ' || r.rolconnlimit),
+               (SELECT string_agg(g.rolname || CASE WHEN m.admin_option THEN ' (admin)' ELSE '' END, ', ' ORDER BY g.rolname)
+                FROM pg_auth_members m JOIN pg_roles g ON g.oid = m.roleid
+                WHERE m.member = r.oid),
+               (SELECT string_agg(u.rolname || CASE WHEN m.admin_option THEN ' (admin)' ELSE '' END, ', ' ORDER BY u.rolname)
+                FROM pg_auth_members m JOIN pg_roles u ON u.oid = m.member
+                WHERE m.roleid = r.oid)
+        FROM pg_roles r
+        WHERE r.rolname = $1
+    ";
+    for role in roles {
+        if verbose >= 1 {
+            println!("[SQL] {} -- params: [{}]", sql.trim(), role);
+        }
+        let Some(row) = client.query_opt(sql, &[role])
+            .await
+            .context("Failed to query role")?
+        else {
+            continue;
+        };
+        let scope = format!("role '{}'", role);
+        let attributes: String = row.get(0);
+        let member_of: Option<String> = row.get(1);
+        let members: Option<String> = row.get(2);
+        facts.insert((scope.clone(), "attributes".to_string()), attributes);
+        facts.insert((scope.clone(), "member of".to_string()), member_of.unwrap_or_else(|| "(none)".to_string()));
+        facts.insert((scope, "members".to_string()), members.unwrap_or_else(|| "(none)".to_string()));
+    }
+    Ok(())
+}
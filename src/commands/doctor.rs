@@ -0,0 +1,415 @@
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::catalog::{discover_metadata, load_mappings, pattern_matches, role_members, user_databases, Mapping};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::{CheckReport, CheckStatus};
+use crate::sql_templates::{quote_identifier, MetadataNames, SqlTemplates, ADDED_CONFIG_COLUMNS};
+
+/// Checks every part `init` installs, database by database. Fails (nonzero exit)
+/// when any check fails; warnings alone do not.
+pub async fn execute(conn_opts: ConnectionConfig, database: Option<String>, verbose: u8) -> Result<()> {
+    let mut report = CheckReport::new("Doctor");
+
+    let explicit = database.is_some();
+    let databases = match database {
+        Some(database) => vec![database],
+        None => {
+            let mut config = conn_opts.clone();
+            config.dbname = Some("postgres".to_string());
+            let client = connect(&config).await?;
+            user_databases(&client, verbose).await?
+        }
+    };
+
+    let mut checked = 0;
+    for database in &databases {
+        let mut config = conn_opts.clone();
+        config.dbname = Some(database.clone());
+        let client = match connect(&config).await {
+            Ok(client) => client,
+            Err(e) => {
+                // Unreadable databases fail the run instead of being skipped
+                checked += 1;
+                let error = format!("{:#}", e).replace('\n', " ");
+                report.section(format!("Database '{}'", database));
+                report.fail(
+                    format!("Connect to database '{}' ({})", database, error),
+                    "Check that the database accepts connections and that the user has CONNECT on it",
+                );
+                continue;
+            }
+        };
+        let capabilities = server_capabilities(&client).await?;
+
+        let Some(metadata) = discover_metadata(&client, verbose).await? else {
+            if explicit {
+                report.section(format!("Database '{}'", database));
+                report.fail(
+                    "Config table present",
+                    format!("Run 'init --database {}' to install the pattern", database),
+                );
+            } else if verbose >= 1 {
+                println!("  No schema_ownership_config in database '{}'", database);
+            }
+            continue;
+        };
+        checked += 1;
+
+        report.section(format!("Database '{}'", database));
+        report.pass(format!("Config table {}", metadata.qualified_config_table()));
+
+        // Templates are only used to render what `init` would install here
+        let templates = SqlTemplates::new(database.clone(), String::new(), String::new(), metadata.clone(), capabilities);
+
+        check_config_columns(&client, &metadata, &mut report, verbose).await?;
+
+        let function = fetch_function(&client, &metadata.schema, &metadata.function, verbose).await?;
+        check_function(&mut report, "Trigger function", &metadata.qualified_function(), function, &templates.create_trigger_function());
+        check_event_trigger(&client, &mut report, &metadata.event_trigger, "ddl_command_end", &metadata, &metadata.function, verbose).await?;
+
+        let function = fetch_function(&client, &metadata.schema, &metadata.cleanup_function, verbose).await?;
+        check_function(&mut report, "Cleanup function", &metadata.qualified_cleanup_function(), function, &templates.create_cleanup_function());
+        check_event_trigger(&client, &mut report, &metadata.cleanup_event_trigger, "sql_drop", &metadata, &metadata.cleanup_function, verbose).await?;
+
+        let mappings = load_mappings(&client, &metadata, verbose).await?;
+        if mappings.is_empty() {
+            report.warn("Mappings", format!("No schema is mapped yet; run 'init --database {}' with --schema", database));
+        }
+        let has_patterns = mappings.iter().any(|m| m.is_pattern);
+        let matches = if has_patterns {
+            pattern_matches(&client, &metadata, verbose).await?
+        } else {
+            Vec::new()
+        };
+
+        for mapping in &mappings {
+            if !mapping.active {
+                report.warn(
+                    format!("Mapping '{}' -> '{}' is inactive (schema was dropped)", mapping.schema_name, mapping.target_role),
+                    format!("Re-run 'init --schema {}' to take over the schema name, or delete the row", mapping.schema_name),
+                );
+                continue;
+            }
+
+            if !mapping.role_exists {
+                report.fail(
+                    format!("Role '{}' for '{}' exists", mapping.target_role, mapping.schema_name),
+                    format!(
+                        "Recreate the role (CREATE ROLE {} NOLOGIN) and re-run 'init' for the schema",
+                        quote_identifier(&mapping.target_role)
+                    ),
+                );
+                continue;
+            }
+
+            if mapping.is_pattern {
+                let matched: Vec<&str> = matches
+                    .iter()
+                    .filter(|(pattern, _)| *pattern == mapping.schema_name)
+                    .map(|(_, schema)| schema.as_str())
+                    .collect();
+                report.pass(format!(
+                    "Pattern '{}' -> '{}' ({} matching schema(s))",
+                    mapping.schema_name, mapping.target_role, matched.len()
+                ));
+                // Schemas that existed before the pattern was mapped keep their owner
+                for schema in matched {
+                    check_schema_owner(&client, &mut report, schema, &mapping.target_role, CheckStatus::Warn, verbose).await?;
+                }
+                continue;
+            }
+
+            check_mapping(&client, &mut report, database, mapping, verbose).await?;
+        }
+    }
+
+    if checked == 0 && !explicit {
+        println!("No databases with schema ownership management found.");
+        return Ok(());
+    }
+
+    report.print_summary();
+
+    let failed = report.count(CheckStatus::Fail);
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+
+    Ok(())
+}
+
+async fn check_config_columns(client: &Client, metadata: &MetadataNames, report: &mut CheckReport, verbose: u8) -> Result<()> {
+    let sql = "
+        SELECT a.attname::text
+        FROM pg_attribute a
+        WHERE a.attrelid = to_regclass($1)
+          AND a.attnum > 0
+          AND NOT a.attisdropped
+    ";
+    let table = metadata.qualified_config_table();
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), table);
+    }
+    let rows = client.query(sql, &[&table])
+        .await
+        .context("Failed to read config table columns")?;
+    let columns: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+
+    let missing: Vec<&str> = ADDED_CONFIG_COLUMNS
+        .iter()
+        .map(|(column, _)| *column)
+        .filter(|column| !columns.iter().any(|c| c == column))
+        .collect();
+    if missing.is_empty() {
+        report.pass("Config table columns up to date");
+    } else {
+        report.warn(
+            format!("Config table is missing columns: {}", missing.join(", ")),
+            "Re-run 'init' to upgrade the config table",
+        );
+    }
+    Ok(())
+}
+
+/// Source and SECURITY DEFINER flag of a zero-argument function, if it exists.
+async fn fetch_function(client: &Client, schema: &str, name: &str, verbose: u8) -> Result<Option<(String, bool)>> {
+    let sql = "
+        SELECT p.prosrc, p.prosecdef
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE n.nspname = $1
+          AND p.proname = $2
+          AND p.pronargs = 0
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, name);
+    }
+    let row = client.query_opt(sql, &[&schema, &name])
+        .await
+        .context("Failed to look up function")?;
+    Ok(row.map(|row| (row.get(0), row.get(1))))
+}
+
+fn check_function(report: &mut CheckReport, label: &str, qualified_name: &str, function: Option<(String, bool)>, expected_sql: &str) {
+    let Some((source, security_definer)) = function else {
+        report.fail(format!("{} {} exists", label, qualified_name), "Re-run 'init' to recreate it");
+        return;
+    };
+
+    // The stored source is the text between the dollar quotes
+    let expected = expected_sql.split("$$").nth(1).unwrap_or_default();
+    if source == expected {
+        report.pass(format!("{} {} matches the installed version", label, qualified_name));
+    } else {
+        report.warn(
+            format!("{} {} differs from what init installs (edited, or from another tool version)", label, qualified_name),
+            "Re-run 'init' to restore it",
+        );
+    }
+
+    if !security_definer {
+        report.fail(
+            format!("{} {} is SECURITY DEFINER", label, qualified_name),
+            "Re-run 'init' to restore it; otherwise it runs with the privileges of whoever issues the DDL",
+        );
+    }
+}
+
+async fn check_event_trigger(
+    client: &Client,
+    report: &mut CheckReport,
+    trigger: &str,
+    event: &str,
+    metadata: &MetadataNames,
+    function: &str,
+    verbose: u8,
+) -> Result<()> {
+    let sql = "
+        SELECT e.evtevent::text, e.evtenabled::text, n.nspname::text, p.proname::text
+        FROM pg_event_trigger e
+        JOIN pg_proc p ON p.oid = e.evtfoid
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE e.evtname = $1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), trigger);
+    }
+    let row = client.query_opt(sql, &[&trigger])
+        .await
+        .context("Failed to look up event trigger")?;
+
+    let Some(row) = row else {
+        report.fail(format!("Event trigger '{}' exists", trigger), "Re-run 'init' to recreate it");
+        return Ok(());
+    };
+    let actual_event: String = row.get(0);
+    let enabled: String = row.get(1);
+    let function_schema: String = row.get(2);
+    let function_name: String = row.get(3);
+
+    if actual_event != event || function_schema != metadata.schema || function_name != function {
+        report.fail(
+            format!(
+                "Event trigger '{}' runs {}.{}() on {} (expected {}.{}() on {})",
+                trigger, function_schema, function_name, actual_event, metadata.schema, function, event
+            ),
+            format!("DROP EVENT TRIGGER {} and re-run 'init'", quote_identifier(trigger)),
+        );
+        return Ok(());
+    }
+
+    // evtenabled: O = origin and local (the default), R = replica only, A = always, D = disabled
+    match enabled.as_str() {
+        "D" => report.fail(
+            format!("Event trigger '{}' is disabled", trigger),
+            format!("ALTER EVENT TRIGGER {} ENABLE", quote_identifier(trigger)),
+        ),
+        "R" => report.warn(
+            format!("Event trigger '{}' only fires in replica sessions", trigger),
+            format!("ALTER EVENT TRIGGER {} ENABLE", quote_identifier(trigger)),
+        ),
+        _ => report.pass(format!("Event trigger '{}' enabled on {}", trigger, event)),
+    }
+    Ok(())
+}
+
+/// Checks that `schema` exists and is owned by `role`; an unexpected owner is
+/// reported with `mismatch` status. Returns whether the schema exists.
+async fn check_schema_owner(
+    client: &Client,
+    report: &mut CheckReport,
+    schema: &str,
+    role: &str,
+    mismatch: CheckStatus,
+    verbose: u8,
+) -> Result<bool> {
+    let sql = "SELECT pg_get_userbyid(nspowner)::text FROM pg_namespace WHERE nspname = $1";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, schema);
+    }
+    let row = client.query_opt(sql, &[&schema])
+        .await
+        .context("Failed to look up schema owner")?;
+
+    let Some(row) = row else {
+        report.fail(
+            format!("Schema '{}' exists", schema),
+            format!("Re-run 'init --schema {}' to recreate it, or delete the mapping", schema),
+        );
+        return Ok(false);
+    };
+
+    let owner: String = row.get(0);
+    if owner == role {
+        report.pass(format!("Schema '{}' owned by '{}'", schema, role));
+    } else {
+        let description = format!("Schema '{}' owned by '{}' instead of '{}'", schema, owner, role);
+        let hint = format!("ALTER SCHEMA {} OWNER TO {}", quote_identifier(schema), quote_identifier(role));
+        if mismatch == CheckStatus::Fail {
+            report.fail(description, hint);
+        } else {
+            report.warn(description, hint);
+        }
+    }
+    Ok(true)
+}
+
+/// Checks the schema, grants and default privileges of an active single-schema mapping.
+async fn check_mapping(client: &Client, report: &mut CheckReport, database: &str, mapping: &Mapping, verbose: u8) -> Result<()> {
+    let schema = &mapping.schema_name;
+    let role = &mapping.target_role;
+
+    if !check_schema_owner(client, report, schema, role, CheckStatus::Fail, verbose).await? {
+        return Ok(());
+    }
+
+    let sql = "
+        SELECT has_database_privilege($2, current_database(), 'CONNECT'),
+               has_schema_privilege($2, $1, 'USAGE'),
+               has_schema_privilege($2, $1, 'CREATE'),
+               (SELECT count(*)
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE n.nspname = $1
+                  AND c.relkind IN ('r', 'p', 'v', 'm', 'f', 'S')
+                  AND NOT CASE WHEN c.relkind = 'S'
+                               THEN has_sequence_privilege($2, c.oid, 'USAGE')
+                               ELSE has_table_privilege($2, c.oid, 'SELECT')
+                          END)
+               + (SELECT count(*)
+                  FROM pg_proc p
+                  JOIN pg_namespace n ON n.oid = p.pronamespace
+                  WHERE n.nspname = $1
+                    AND NOT has_function_privilege($2, p.oid, 'EXECUTE'))
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, role);
+    }
+    let row = client.query_one(sql, &[schema, role])
+        .await
+        .context("Failed to check privileges")?;
+    let can_connect: bool = row.get(0);
+    let has_usage: bool = row.get(1);
+    let has_create: bool = row.get(2);
+    let objects_without_access: i64 = row.get(3);
+
+    let regrant = format!("Re-run 'init --database {} --schema {} --role {}' to restore grants", database, schema, role);
+    if can_connect {
+        report.pass(format!("'{}' can connect to '{}'", role, database));
+    } else {
+        report.fail(format!("'{}' can connect to '{}'", role, database), &regrant);
+    }
+    if has_usage && has_create {
+        report.pass(format!("'{}' has USAGE and CREATE on schema '{}'", role, schema));
+    } else {
+        report.fail(format!("'{}' has USAGE and CREATE on schema '{}'", role, schema), &regrant);
+    }
+    if objects_without_access == 0 {
+        report.pass(format!("'{}' has access to existing objects in '{}'", role, schema));
+    } else {
+        report.warn(
+            format!("'{}' lacks privileges on {} existing object(s) in '{}'", role, objects_without_access, schema),
+            &regrant,
+        );
+    }
+
+    // Default privileges are expected for the target role and each of its members
+    let sql = "
+        SELECT r.rolname::text
+        FROM pg_default_acl d
+        JOIN pg_namespace n ON n.oid = d.defaclnamespace
+        JOIN pg_roles r ON r.oid = d.defaclrole
+        CROSS JOIN LATERAL aclexplode(d.defaclacl) a
+        JOIN pg_roles g ON g.oid = a.grantee
+        WHERE n.nspname = $1
+          AND g.rolname = $2
+        GROUP BY r.rolname
+        HAVING count(DISTINCT d.defaclobjtype) FILTER (WHERE d.defaclobjtype IN ('r', 'S', 'f')) = 3
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, role);
+    }
+    let rows = client.query(sql, &[schema, role])
+        .await
+        .context("Failed to query default privileges")?;
+    let complete: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+
+    let mut expected = vec![role.clone()];
+    expected.extend(role_members(client, role, verbose).await?);
+    let missing: Vec<&String> = expected.iter().filter(|grantor| !complete.contains(grantor)).collect();
+    if missing.is_empty() {
+        report.pass(format!("Default privileges in '{}' cover '{}' and its members", schema, role));
+    } else {
+        report.warn(
+            format!(
+                "Default privileges in '{}' missing for: {}",
+                schema,
+                missing.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+            format!("Run 'sync-default-privileges --database {} --schema {}'", database, schema),
+        );
+    }
+
+    Ok(())
+}
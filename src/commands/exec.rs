@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{discover_metadata, managed_schemas};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::sql_templates::quote_identifier;
+
+/// The command `exec` runs, and the schema whose target role it runs as.
+#[derive(Debug)]
+pub struct ExecOptions {
+    pub database: String,
+    pub schema: String,
+    /// Program and arguments
+    pub command: Vec<String>,
+}
+
+/// Runs a command with the PG* environment pointing at the database and
+/// `PGOPTIONS` switching its sessions to the schema's target role. Returns the
+/// command's exit code.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ExecOptions, verbose: u8) -> Result<i32> {
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+
+    // Migration users are often not the administrator that ran init
+    let config_table = metadata.qualified_config_table();
+    let sql = "SELECT has_table_privilege($1, 'SELECT')";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, config_table);
+    }
+    let can_read: bool = client.query_one(sql, &[&config_table])
+        .await
+        .context("Failed to check access to the config table")?
+        .get(0);
+    if !can_read {
+        anyhow::bail!(
+            "'{}' cannot read {}; grant it SELECT on the table, or connect as a user that can",
+            config.user, config_table
+        );
+    }
+
+    let managed = managed_schemas(&client, &metadata, verbose).await?
+        .into_iter()
+        .find(|m| m.schema == opts.schema)
+        .ok_or_else(|| anyhow::anyhow!(
+            "Schema '{}' is not managed in database '{}' (see 'list-mappings')",
+            opts.schema, opts.database
+        ))?;
+    if !managed.role_exists {
+        anyhow::bail!("Target role '{}' of schema '{}' does not exist", managed.target_role, opts.schema);
+    }
+
+    // Before PostgreSQL 16 any member may SET ROLE; from 16 the grant's SET option decides
+    let privilege = if capabilities.has_membership_options() { "SET" } else { "MEMBER" };
+    let sql = "SELECT pg_has_role(current_user, $1, $2)";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql, managed.target_role, privilege);
+    }
+    let can_set_role: bool = client.query_one(sql, &[&managed.target_role, &privilege])
+        .await
+        .context("Failed to check role membership")?
+        .get(0);
+    if !can_set_role {
+        anyhow::bail!(
+            "'{}' cannot SET ROLE to '{}'; grant it with 'grant-member --schema {} --member {}'",
+            config.user, managed.target_role, opts.schema, config.user
+        );
+    }
+    drop(client);
+
+    let mut options = format!(
+        "-c role={} -c search_path={},public",
+        escape_option(&managed.target_role),
+        escape_option(&quote_identifier(&opts.schema))
+    );
+    // Options already in the environment still apply
+    if let Ok(existing) = std::env::var("PGOPTIONS")
+        && !existing.trim().is_empty()
+    {
+        options = format!("{} {}", existing.trim(), options);
+    }
+
+    let (program, args) = opts.command.split_first()
+        .ok_or_else(|| anyhow::anyhow!("No command given; put it after '--'"))?;
+    if verbose >= 1 {
+        eprintln!("Running '{}' as '{}' in {}.{} (PGOPTIONS={})", program, managed.target_role, opts.database, opts.schema, options);
+    }
+
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(args)
+        .env("PGHOST", &config.host)
+        .env("PGPORT", config.port.to_string())
+        .env("PGUSER", &config.user)
+        .env("PGDATABASE", &opts.database)
+        .env("PGSSLMODE", config.sslmode.as_str())
+        .env("PGOPTIONS", &options);
+    if !config.password.is_empty() {
+        command.env("PGPASSWORD", &config.password);
+    }
+    let status = command.status()
+        .await
+        .with_context(|| format!("Failed to run '{}'", program))?;
+
+    Ok(exit_code(status))
+}
+
+/// Escapes spaces and backslashes, which separate and quote words in `PGOPTIONS`.
+fn escape_option(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(' ', "\\ ")
+}
+
+/// The child's exit code; a child killed by a signal exits 128 + signal, as in the shell.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
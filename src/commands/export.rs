@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::catalog::cluster_mappings;
+use crate::db::ConnectionConfig;
+use crate::manifest::{self, DatabaseManifest, Manifest, MappingManifest, MetadataManifest};
+
+/// Where `export` writes the manifest and in which format.
+#[derive(Debug)]
+pub struct ExportOptions {
+    /// `None` writes to stdout
+    pub output: Option<PathBuf>,
+    /// "toml" or "yaml"; taken from the output file's extension when unset
+    pub format: Option<String>,
+}
+
+/// Writes every active mapping of the instance as a manifest that `import` and
+/// `apply` read.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ExportOptions, verbose: u8) -> Result<()> {
+    let yaml = match opts.format.as_deref() {
+        Some("toml") => false,
+        Some("yaml" | "yml") => true,
+        Some(other) => anyhow::bail!("Unknown format '{}'; use toml or yaml", other),
+        None => match opts.output.as_ref().and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            None | Some("toml") => false,
+            Some("yaml" | "yml") => true,
+            Some(_) => anyhow::bail!("Cannot tell the format from the output file name; pass --format"),
+        },
+    };
+
+    let cluster = cluster_mappings(&conn_opts, verbose).await?;
+    // A manifest missing a database would make 'apply --prune' remove its mappings elsewhere
+    if !cluster.unreachable.is_empty() {
+        anyhow::bail!(
+            "Could not read {} database(s): {}. No manifest was written.",
+            cluster.unreachable.len(),
+            cluster.unreachable.join(", ")
+        );
+    }
+
+    let mut manifest = Manifest { databases: Vec::new() };
+    let mut count = 0;
+    for database in cluster.databases {
+        let mut mappings = Vec::new();
+        for mapping in database.mappings {
+            // An inactive mapping's schema was dropped; importing it would recreate the schema
+            if !mapping.active {
+                eprintln!("Skipped inactive mapping {}.{}", database.database, mapping.schema_name);
+                continue;
+            }
+            let (schema, schema_pattern) = if mapping.is_pattern {
+                (None, Some(mapping.schema_name))
+            } else {
+                (Some(mapping.schema_name), None)
+            };
+            let manifest_mapping = MappingManifest {
+                schema,
+                schema_pattern,
+                role: mapping.target_role,
+                report_extension_owner: Some(mapping.report_extension_owner),
+                on_missing_role: Some(mapping.on_missing_role),
+                preset: None,
+                migrator_role: None,
+                runtime_role: None,
+                readonly_role: None,
+                created_at: mapping.created_at.map(|t| t.to_rfc3339()),
+                updated_at: mapping.updated_at.map(|t| t.to_rfc3339()),
+            };
+            mappings.push(manifest_mapping.with_preset_roles(mapping.preset.as_ref()));
+        }
+        if mappings.is_empty() {
+            continue;
+        }
+        count += mappings.len();
+        manifest.databases.push(DatabaseManifest {
+            name: database.database,
+            metadata: MetadataManifest::from_names(&database.metadata),
+            mappings,
+        });
+    }
+
+    let header = format!(
+        "# Exported by pg-app-role-manager {} from {}:{}\n",
+        env!("CARGO_PKG_VERSION"),
+        conn_opts.host,
+        conn_opts.port
+    );
+    let content = format!("{}{}", header, manifest::render(&manifest, yaml)?);
+    match &opts.output {
+        Some(path) => {
+            std::fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote {} mapping(s) in {} database(s) to {}", count, manifest.databases.len(), path.display());
+        }
+        None => print!("{}", content),
+    }
+
+    Ok(())
+}
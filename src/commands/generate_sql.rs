@@ -0,0 +1,247 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::catalog::BLOCKED_DATABASES;
+use crate::commands::init::MetadataOptions;
+use crate::db::{ServerCapabilities, MIN_SERVER_VERSION_NUM};
+use crate::sql_templates::{quote_literal, MappingOptions, MetadataNames, SqlTemplates};
+
+/// What `generate-sql` renders; the same choices `init` takes.
+#[derive(Debug)]
+pub struct GenerateSqlOptions {
+    pub database: String,
+    /// Schema name, or a LIKE pattern when `schema_is_pattern` is set
+    pub schema: String,
+    pub schema_is_pattern: bool,
+    pub role: String,
+    pub metadata: MetadataOptions,
+    pub mapping: MappingOptions,
+    /// Major version of the server the script will run on
+    pub server_version: i32,
+    /// `None` writes to stdout
+    pub output: Option<PathBuf>,
+}
+
+/// Writes the statements `init` would run as a psql script, with existence
+/// checks in place of the checks `init` makes against the server.
+pub fn execute(opts: GenerateSqlOptions) -> Result<()> {
+    if BLOCKED_DATABASES.contains(&opts.database.as_str()) {
+        anyhow::bail!(
+            "Cannot initialize schema ownership management on system database '{}'. \
+             System databases (postgres, template0, template1, rdsadmin, etc.) are reserved for internal use.",
+            opts.database
+        );
+    }
+    if opts.schema_is_pattern && !opts.schema.contains(['%', '_']) {
+        anyhow::bail!(
+            "Schema pattern '{}' contains no LIKE wildcard ('%' or '_'). Use --schema to map a single schema.",
+            opts.schema
+        );
+    }
+    if opts.server_version * 10000 < MIN_SERVER_VERSION_NUM {
+        anyhow::bail!(
+            "PostgreSQL {} is not supported. Version {} or newer is required.",
+            opts.server_version,
+            MIN_SERVER_VERSION_NUM / 10000
+        );
+    }
+
+    let script = render(&opts);
+    match &opts.output {
+        Some(path) => {
+            std::fs::write(path, script)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote {}", path.display());
+        }
+        None => print!("{}", script),
+    }
+
+    Ok(())
+}
+
+fn render(opts: &GenerateSqlOptions) -> String {
+    let metadata = opts.metadata.resolve(&MetadataNames::default());
+    let mut templates = SqlTemplates::new(
+        opts.database.clone(),
+        opts.schema.clone(),
+        opts.role.clone(),
+        metadata.clone(),
+        ServerCapabilities::from_version_num(opts.server_version * 10000),
+    );
+    templates.options = opts.mapping.clone();
+    templates.schema_is_pattern = opts.schema_is_pattern;
+
+    let schema_literal = quote_literal(&opts.schema);
+    let role_literal = quote_literal(&opts.role);
+    let config_table = metadata.qualified_config_table();
+
+    // Writes to a String cannot fail
+    let mut script = String::new();
+    let _ = writeln!(
+        script,
+        "-- pg-app-role-manager {}: init for {} '{}' -> role '{}' in database '{}'",
+        env!("CARGO_PKG_VERSION"),
+        if opts.schema_is_pattern { "schema pattern" } else { "schema" },
+        opts.schema,
+        opts.role,
+        opts.database
+    );
+    let _ = writeln!(script, "-- Generated for PostgreSQL {}. Run with psql 10 or later:", opts.server_version);
+    let _ = writeln!(script, "--   psql -f <this file>");
+    let _ = writeln!(script, "-- Safe to re-run; steps already in place are skipped or re-applied unchanged.");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "\\set ON_ERROR_STOP on");
+
+    let _ = writeln!(script, "\n-- Database");
+    let _ = writeln!(script, "\\connect postgres");
+    let _ = writeln!(
+        script,
+        "SELECT NOT EXISTS (SELECT 1 FROM pg_database WHERE datname = {}) AS database_missing \\gset",
+        quote_literal(&opts.database)
+    );
+    let _ = writeln!(script, "\\if :database_missing\n{};\n\\endif", templates.create_database());
+
+    let _ = writeln!(script, "\n\\connect {}", quote_literal(&opts.database));
+
+    // New databases on pre-15 servers get the locked-down public schema of 15+
+    if let Some(sql) = templates.revoke_public_schema_create() {
+        let _ = writeln!(script, "\\if :database_missing\n{};\n\\endif", sql);
+    }
+
+    // The checks init makes before changing anything
+    let _ = writeln!(script, "\n-- Existing mapping");
+    let schema_exists = format!("EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = {})", schema_literal);
+    let _ = writeln!(
+        script,
+        "SELECT NOT {} AS schema_missing \\gset",
+        if opts.schema_is_pattern { "false".to_string() } else { schema_exists.clone() }
+    );
+    let _ = writeln!(
+        script,
+        r#"DO $guard$
+DECLARE
+    existing record;
+BEGIN
+    IF to_regclass({config_table_literal}) IS NULL THEN
+        RETURN;
+    END IF;
+    SELECT c.target_role,
+           COALESCE((to_jsonb(c) ->> 'active')::boolean, true) AS active,
+           COALESCE((to_jsonb(c) ->> 'is_pattern')::boolean, false) AS is_pattern
+    INTO existing
+    FROM {config_table} c
+    WHERE c.schema_name = {schema};
+    IF NOT FOUND THEN
+        RETURN;
+    END IF;
+    IF existing.is_pattern <> {is_pattern} THEN
+        RAISE EXCEPTION '% is already mapped with is_pattern = %', {schema}, existing.is_pattern;
+    END IF;
+    IF existing.active AND existing.target_role <> {role} AND ({is_pattern} OR {schema_exists}) THEN
+        RAISE EXCEPTION '% is already mapped to role %; use remap to move it', {schema}, existing.target_role;
+    END IF;
+END
+$guard$;"#,
+        config_table_literal = quote_literal(&config_table),
+        config_table = config_table,
+        schema = schema_literal,
+        role = role_literal,
+        is_pattern = opts.schema_is_pattern,
+        schema_exists = schema_exists
+    );
+
+    if !opts.schema_is_pattern {
+        let _ = writeln!(script, "\n-- Schema");
+        let _ = writeln!(script, "{}", guarded(&schema_exists, &templates.create_schema()));
+    }
+
+    let _ = writeln!(script, "\n-- Role");
+    let _ = writeln!(
+        script,
+        "{}",
+        guarded(&format!("EXISTS (SELECT 1 FROM pg_roles WHERE rolname = {})", role_literal), &templates.create_role())
+    );
+    let _ = writeln!(script, "{};", templates.grant_connect());
+
+    // Schemas matching a pattern are handed to the role by the event trigger
+    if !opts.schema_is_pattern {
+        let _ = writeln!(script, "\n-- Schema ownership and grants");
+        for sql in [
+            templates.alter_schema_owner(),
+            templates.grant_schema_usage(),
+            templates.grant_schema_create(),
+            templates.grant_all_tables(),
+            templates.grant_all_sequences(),
+            templates.grant_all_functions(),
+            templates.alter_default_privileges_tables(),
+            templates.alter_default_privileges_sequences(),
+            templates.alter_default_privileges_functions(),
+        ] {
+            let _ = writeln!(script, "{};", sql);
+        }
+
+        let _ = writeln!(script, "\n-- Default privileges for the role and its current members");
+        for sql in templates.alter_default_privileges_for_role(&opts.role) {
+            let _ = writeln!(script, "{};", sql);
+        }
+        let _ = writeln!(script, "{}\n\\gexec", templates.alter_default_privileges_for_members_query());
+    }
+
+    let _ = writeln!(script, "\n-- Metadata schema and config table");
+    let _ = writeln!(script, "{};", templates.create_metadata_schema());
+    let _ = writeln!(script, "{};", templates.create_config_table());
+    for sql in templates.upgrade_config_table() {
+        let _ = writeln!(script, "{};", sql);
+    }
+    let _ = writeln!(script, "{};", templates.comment_config_table());
+
+    let _ = writeln!(script, "\n-- Trigger functions and event triggers");
+    let _ = writeln!(script, "{};", templates.create_trigger_function());
+    let _ = writeln!(script, "{};", templates.comment_trigger_function());
+    let _ = writeln!(script, "{}", guarded(&event_trigger_exists(&metadata.event_trigger), &templates.create_event_trigger()));
+    let _ = writeln!(script, "{};", templates.create_cleanup_function());
+    let _ = writeln!(script, "{};", templates.comment_cleanup_function());
+    let _ = writeln!(
+        script,
+        "{}",
+        guarded(&event_trigger_exists(&metadata.cleanup_event_trigger), &templates.create_cleanup_event_trigger())
+    );
+
+    // A mapping deactivated by the drop trigger, or left behind by a dropped
+    // schema, is taken over; decided before the insert below
+    let _ = writeln!(script, "\n-- Mapping");
+    let _ = writeln!(
+        script,
+        "SELECT EXISTS (SELECT 1 FROM {} WHERE schema_name = {} AND (NOT active OR :'schema_missing'::boolean)) AS reactivate \\gset",
+        config_table, schema_literal
+    );
+    let _ = writeln!(script, "{};", templates.insert_initial_mapping());
+    let _ = writeln!(script, "\\if :reactivate\n{};\n\\endif", templates.reactivate_mapping());
+    if let Some(sql) = templates.update_mapping_options() {
+        let _ = writeln!(script, "{};", sql);
+    }
+    // The same members the \gexec above gave default privileges to
+    if !opts.schema_is_pattern {
+        for sql in templates.record_default_privilege_grantors(std::slice::from_ref(&opts.role)) {
+            let _ = writeln!(script, "{};", sql);
+        }
+        let _ = writeln!(script, "{};", templates.record_member_default_privilege_grantors());
+    }
+
+    script
+}
+
+/// Runs `sql` unless `exists` (a boolean SQL expression) holds.
+fn guarded(exists: &str, sql: &str) -> String {
+    format!(
+        "DO $guard$\nBEGIN\n    IF NOT {} THEN\n        {};\n    END IF;\nEND\n$guard$;",
+        exists,
+        sql.replace('\n', "\n        ")
+    )
+}
+
+fn event_trigger_exists(name: &str) -> String {
+    format!("EXISTS (SELECT 1 FROM pg_event_trigger WHERE evtname = {})", quote_literal(name))
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::catalog::cluster_mappings;
+use crate::commands::init::InitOptions;
+use crate::db::ConnectionConfig;
+use crate::manifest;
+use crate::report::{ActionOutcome, ActionReport};
+
+/// The manifest `import` replays.
+#[derive(Debug)]
+pub struct ImportOptions {
+    pub manifest: PathBuf,
+}
+
+/// Runs `init` for every mapping in a manifest, typically one written by
+/// `export` on another instance. A failing mapping does not stop the others;
+/// the command fails at the end if any did.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ImportOptions, verbose: u8) -> Result<()> {
+    let manifest = manifest::load(&opts.manifest)?;
+
+    let live = cluster_mappings(&conn_opts, verbose).await?.databases;
+    let mut report = ActionReport::new("Import");
+    let mut failed = 0;
+
+    for database in &manifest.databases {
+        for mapping in &database.mappings {
+            let (schema, is_pattern) = mapping.schema_name();
+            let label = format!("{}.{} -> {}", database.name, schema, mapping.role);
+            let exists = live.iter().any(|db| {
+                db.database == database.name && db.mappings.iter().any(|m| m.schema_name == schema)
+            });
+
+            let init_opts = InitOptions {
+                database: database.name.clone(),
+                schema: schema.to_string(),
+                schema_is_pattern: is_pattern,
+                role: mapping.role.clone(),
+                metadata: database.metadata_options(),
+                migrate_metadata: false,
+                mapping: mapping.options()?,
+                preset: mapping.preset_roles()?,
+                dry_run: false,
+            };
+
+            println!("==> init {}", label);
+            let outcome = match crate::commands::init::execute(conn_opts.clone(), init_opts, verbose).await {
+                Ok(()) if exists => ActionOutcome::Updated,
+                Ok(()) => ActionOutcome::Created,
+                Err(e) => {
+                    println!("Error: {:#}", e);
+                    failed += 1;
+                    ActionOutcome::Failed
+                }
+            };
+            println!();
+            report.record(label, outcome);
+        }
+    }
+
+    report.print_summary();
+
+    if failed > 0 {
+        anyhow::bail!("{} mapping(s) from {} could not be imported", failed, opts.manifest.display());
+    }
+
+    Ok(())
+}
@@ -1,14 +1,71 @@
 use anyhow::{Context, Result};
 use tokio_postgres::Client;
 
-use crate::db::{connect, ConnectionConfig};
-use crate::report::{ActionOutcome, ActionReport};
-use crate::sql_templates::SqlTemplates;
+use crate::catalog::{
+    default_privilege_types, discover_metadata, has_default_privileges, load_mappings, pattern_matches, role_members,
+    BLOCKED_DATABASES,
+};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::{is_function_body, ActionOutcome, ActionReport, Plan, PlannedAction};
+use crate::sql_templates::{quote_literal, MappingOptions, MetadataNames, MissingRolePolicy, PresetRoles, SchemaAccess, SqlTemplates};
+
+/// Metadata location requested on the command line; unset fields keep the
+/// existing installation's value, or the default for a fresh database.
+#[derive(Debug, Default)]
+pub struct MetadataOptions {
+    pub schema: Option<String>,
+    pub config_table: Option<String>,
+    pub function: Option<String>,
+    pub event_trigger: Option<String>,
+    pub cleanup_function: Option<String>,
+    pub cleanup_event_trigger: Option<String>,
+}
+
+impl MetadataOptions {
+    pub fn resolve(&self, base: &MetadataNames) -> MetadataNames {
+        MetadataNames {
+            schema: self.schema.clone().unwrap_or_else(|| base.schema.clone()),
+            config_table: self.config_table.clone().unwrap_or_else(|| base.config_table.clone()),
+            function: self.function.clone().unwrap_or_else(|| base.function.clone()),
+            event_trigger: self.event_trigger.clone().unwrap_or_else(|| base.event_trigger.clone()),
+            cleanup_function: self.cleanup_function.clone().unwrap_or_else(|| base.cleanup_function.clone()),
+            cleanup_event_trigger: self.cleanup_event_trigger.clone().unwrap_or_else(|| base.cleanup_event_trigger.clone()),
+        }
+    }
+}
+
+/// Everything `init` needs to set up one schema-to-role mapping.
+#[derive(Debug)]
+pub struct InitOptions {
+    pub database: String,
+    /// Schema name, or a LIKE pattern when `schema_is_pattern` is set
+    pub schema: String,
+    pub schema_is_pattern: bool,
+    pub role: String,
+    pub metadata: MetadataOptions,
+    pub migrate_metadata: bool,
+    pub mapping: MappingOptions,
+    /// Migrator, runtime and read-only roles to create next to the owner role
+    pub preset: Option<PresetRoles>,
+    /// Print the plan without making any changes
+    pub dry_run: bool,
+}
+
+pub async fn execute(conn_opts: ConnectionConfig, opts: InitOptions, verbose: u8) -> Result<()> {
+    let InitOptions {
+        database,
+        schema,
+        schema_is_pattern,
+        role,
+        metadata: metadata_opts,
+        migrate_metadata,
+        mapping: mapping_options,
+        preset,
+        dry_run,
+    } = opts;
 
-pub async fn execute(conn_opts: ConnectionConfig, database: String, schema: String, role: String, verbose: u8) -> Result<()> {
     // Block operations on system databases (PostgreSQL + cloud providers)
-    let blocked_databases = ["postgres", "template0", "template1", "rdsadmin", "azure_maintenance", "cloudsqladmin"];
-    if blocked_databases.contains(&database.as_str()) {
+    if BLOCKED_DATABASES.contains(&database.as_str()) {
         anyhow::bail!(
             "Cannot initialize schema ownership management on system database '{}'. \
              System databases (postgres, template0, template1, rdsadmin, etc.) are reserved for internal use.",
@@ -16,167 +73,507 @@ pub async fn execute(conn_opts: ConnectionConfig, database: String, schema: Stri
         );
     }
 
-    let mut report = ActionReport::new("Init");
-    let templates = SqlTemplates::new(database.clone(), schema.clone(), role.clone());
-
-    // Helper to print SQL in verbose mode
-    let log_sql = |sql: &str, min_level: u8| {
-        if verbose >= min_level {
-            println!("[SQL] {}", sql);
-        }
-    };
+    if schema_is_pattern && !schema.contains(['%', '_']) {
+        anyhow::bail!(
+            "Schema pattern '{}' contains no LIKE wildcard ('%' or '_'). Use --schema to map a single schema.",
+            schema
+        );
+    }
+    if schema_is_pattern && preset.is_some() {
+        anyhow::bail!("--preset needs a single schema; schemas matching a pattern only get an owner");
+    }
+    let schema_label = if schema_is_pattern { "Schema pattern" } else { "Schema" };
 
     // Connect to postgres system database
     let mut config = conn_opts.clone();
     config.dbname = Some("postgres".to_string());
-    let client = connect(&config).await?;
+    let server_client = connect(&config).await?;
+    let capabilities = server_capabilities(&server_client).await?;
+    if verbose >= 1 {
+        println!("Server version: {} ({})", capabilities.major_version(), capabilities.version_num);
+    }
+
+    // Metadata location is settled once the target database has been inspected
+    let mut templates = SqlTemplates::new(
+        database.clone(),
+        schema.clone(),
+        role.clone(),
+        MetadataNames::default(),
+        capabilities,
+    );
 
-    // Check and create database
-    if database_exists(&client, &database, verbose).await? {
-        report.record(format!("Database '{}'", database), ActionOutcome::Skipped);
+    // Every check runs before anything is changed; the plan is then either
+    // printed (--dry-run) or applied in order
+    let mut plan = Plan::new(if dry_run { "Init Plan (dry run)" } else { "Init Plan" });
+
+    let database_exists = database_exists(&server_client, &database, verbose).await?;
+    if database_exists {
+        plan.add(format!("Database '{}'", database), ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add(format!("Database '{}'", database), ActionOutcome::Created, vec![templates.create_database()]);
+    }
+
+    // Roles are cluster-wide, so they can be inspected before the database exists
+    let role_exists = role_exists(&server_client, &role, verbose).await?;
+    let role_members = if role_exists { role_members(&server_client, &role, verbose).await? } else { Vec::new() };
+    let connect_granted = role_exists && connect_granted(&server_client, &database, &role, verbose).await?;
+
+    // A database that does not exist yet has nothing installed in it
+    let client = if database_exists {
+        let mut target_config = conn_opts.clone();
+        target_config.dbname = Some(database.clone());
+        Some(connect(&target_config).await?)
     } else {
-        let sql = templates.create_database();
-        log_sql(&sql, 1);
-        client.execute(&sql, &[]).await
-            .context("Failed to create database")?;
-        report.record(format!("Database '{}'", database), ActionOutcome::Created);
-    }
-
-    // Reconnect to target database
-    drop(client);
-    let mut target_config = conn_opts.clone();
-    target_config.dbname = Some(database.clone());
-    let client = connect(&target_config).await?;
-
-    // Check and create schema
-    let schema_already_exists = schema_exists(&client, &schema, verbose).await?;
-    if schema_already_exists {
-        // Schema exists - check if there's already a mapping for it
-        if let Some(existing_role) = get_schema_mapping(&client, &schema, verbose).await? {
-            if existing_role != role {
-                anyhow::bail!(
-                    "Schema '{}' is already mapped to role '{}'. Schema-to-role mappings are immutable after initialization. \
-                     To change the mapping, you must manually update the database using SQL.",
-                    schema, existing_role
+        None
+    };
+
+    // New databases on pre-15 servers get the locked-down public schema of 15+
+    if !database_exists && let Some(sql) = templates.revoke_public_schema_create() {
+        plan.add("CREATE on public schema revoked from PUBLIC", ActionOutcome::Updated, vec![sql]);
+    }
+
+    // Find an existing installation, which may live somewhere other than requested
+    let installed = match &client {
+        Some(client) => discover_metadata(client, verbose).await?,
+        None => None,
+    };
+    let metadata = metadata_opts.resolve(installed.as_ref().unwrap_or(&MetadataNames::default()));
+    templates.metadata = metadata.clone();
+    templates.options = mapping_options;
+    templates.schema_is_pattern = schema_is_pattern;
+    if let Some(existing) = &installed
+        && *existing != metadata
+        && !migrate_metadata
+    {
+        anyhow::bail!(
+            "Database '{}' already has the config table at {} (function {}, event trigger '{}'). \
+             Re-run with --migrate-metadata to move the installation to {}.",
+            database,
+            existing.qualified_config_table(),
+            existing.qualified_function(),
+            existing.event_trigger,
+            metadata.qualified_config_table()
+        );
+    }
+
+    let mut existing_mapping = None;
+    if let (Some(client), Some(existing)) = (&client, &installed) {
+        // Preflight: mappings whose target role has been dropped no longer transfer ownership
+        for mapping in load_mappings(client, existing, verbose).await? {
+            if mapping.active && !mapping.role_exists {
+                println!(
+                    "Warning: Schema '{}' is mapped to role '{}', which no longer exists (on_missing_role: {}). \
+                     Recreate the role or re-run 'init' for that schema.",
+                    mapping.schema_name, mapping.target_role, mapping.on_missing_role
                 );
             }
-            // else: Same role, continue idempotently
         }
-        report.record(format!("Schema '{}'", schema), ActionOutcome::Skipped);
+
+        existing_mapping = get_schema_mapping(client, existing, &schema, verbose).await?;
+    }
+
+    if let Some(existing) = &existing_mapping
+        && existing.is_pattern != schema_is_pattern
+    {
+        anyhow::bail!(
+            "'{}' is already mapped as a {}. Use {} to re-run init for it.",
+            schema,
+            if existing.is_pattern { "schema pattern" } else { "single schema" },
+            if existing.is_pattern { "--schema-pattern" } else { "--schema" }
+        );
+    }
+
+    // Patterns only match schemas, they never create one
+    let schema_state = match &client {
+        Some(client) if !schema_is_pattern => schema_state(client, &schema, &role, verbose).await?,
+        _ => None,
+    };
+    let schema_already_exists = schema_state.is_some();
+
+    // A mapping deactivated by the drop trigger, or left behind by a schema dropped
+    // before that trigger existed, no longer protects anything and is taken over
+    let orphaned_mapping = matches!(
+        &existing_mapping,
+        Some(existing) if !existing.active || (!schema_is_pattern && !schema_already_exists)
+    );
+
+    if schema_already_exists || schema_is_pattern {
+        // Check if there's already a mapping for the schema or pattern
+        if let Some(existing) = &existing_mapping
+            && existing.active
+            && existing.role != role
+        {
+            let hint = if schema_is_pattern {
+                "Pattern mappings cannot be changed; uninstall it and run init again.".to_string()
+            } else {
+                format!("Use 'remap --schema {} --from {} --to {}' to move it to the new role.", schema, existing.role, role)
+            };
+            anyhow::bail!("{} '{}' is already mapped to role '{}'. {}", schema_label, schema, existing.role, hint);
+        }
+        if schema_already_exists {
+            plan.add(format!("Schema '{}'", schema), ActionOutcome::Skipped, Vec::new());
+        }
+    } else {
+        plan.add(format!("Schema '{}'", schema), ActionOutcome::Created, vec![templates.create_schema()]);
+    }
+
+    if role_exists {
+        plan.add(format!("Role '{}'", role), ActionOutcome::Skipped, Vec::new());
     } else {
-        let sql = templates.create_schema();
-        log_sql(&sql, 1);
-        client.execute(&sql, &[]).await
-            .context("Failed to create schema")?;
-        report.record(format!("Schema '{}'", schema), ActionOutcome::Created);
+        plan.add(format!("Role '{}'", role), ActionOutcome::Created, vec![templates.create_role()]);
+    }
+
+    if connect_granted {
+        plan.add("CONNECT privilege", ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add("CONNECT privilege", ActionOutcome::Updated, vec![templates.grant_connect()]);
+    }
+
+    // Roles given `FOR ROLE` default privileges, recorded in the mapping
+    let mut grantors = Vec::new();
+
+    // Schemas matching a pattern are handed to the role by the event trigger as
+    // they are created, so there is no single schema to set up here
+    if !schema_is_pattern {
+        // Grants held by a previous owner move with ownership, so they are only
+        // known to be in place when the role already owns the schema
+        let (owned, has_usage, has_create) = schema_state
+            .as_ref()
+            .map_or((false, false, false), |state| (state.owner == role, state.has_usage, state.has_create));
+
+        let (outcome, statements) = if owned {
+            (ActionOutcome::Skipped, Vec::new())
+        } else {
+            (ActionOutcome::Updated, vec![templates.alter_schema_owner()])
+        };
+        plan.add("Schema ownership", outcome, statements);
+
+        if owned && has_usage {
+            plan.add("USAGE on schema", ActionOutcome::Skipped, Vec::new());
+        } else {
+            plan.add("USAGE on schema", ActionOutcome::Updated, vec![templates.grant_schema_usage()]);
+        }
+        if owned && has_create {
+            plan.add("CREATE on schema", ActionOutcome::Skipped, Vec::new());
+        } else {
+            plan.add("CREATE on schema", ActionOutcome::Updated, vec![templates.grant_schema_create()]);
+        }
+
+        // Cover whatever objects exist at the time, so always re-issued
+        plan.add("ALL on tables", ActionOutcome::Updated, vec![templates.grant_all_tables()]);
+        plan.add("ALL on sequences", ActionOutcome::Updated, vec![templates.grant_all_sequences()]);
+        plan.add("ALL on functions", ActionOutcome::Updated, vec![templates.grant_all_functions()]);
+
+        let current_defaults = match &client {
+            Some(client) if schema_already_exists => default_privilege_types(client, None, &schema, &role, verbose).await?,
+            _ => Vec::new(),
+        };
+        for (objtype, label, sql) in [
+            ("r", "tables", templates.alter_default_privileges_tables()),
+            ("S", "sequences", templates.alter_default_privileges_sequences()),
+            ("f", "functions", templates.alter_default_privileges_functions()),
+        ] {
+            let description = format!("Default privileges for {}", label);
+            if current_defaults.iter().any(|t| t == objtype) {
+                plan.add(description, ActionOutcome::Skipped, Vec::new());
+            } else {
+                plan.add(description, ActionOutcome::Updated, vec![sql]);
+            }
+        }
+
+        // Default privileges only cover objects created by the named role, so issue
+        // them for the target role and every role that can create objects as a member
+        grantors.push(role.clone());
+        grantors.extend(role_members.iter().cloned());
+        for grantor in &grantors {
+            let current = match &client {
+                Some(client) if schema_already_exists && role_exists => {
+                    has_default_privileges(client, grantor, &schema, &role, verbose).await?
+                }
+                _ => false,
+            };
+            let description = format!("Default privileges for role '{}'", grantor);
+            if current {
+                plan.add(description, ActionOutcome::Skipped, Vec::new());
+            } else {
+                plan.add(description, ActionOutcome::Updated, templates.alter_default_privileges_for_role(grantor));
+            }
+        }
+
+        if let Some(preset) = &preset {
+            add_preset_actions(&mut plan, &server_client, &templates, preset, &grantors, &role_members, verbose).await?;
+        }
     }
 
-    // Check and create role
-    if role_exists(&client, &role, verbose).await? {
-        report.record(format!("Role '{}'", role), ActionOutcome::Skipped);
+    // A metadata schema named like the mapped schema is created along with it
+    let metadata_schema_exists = (metadata.schema == schema && !schema_is_pattern)
+        || match &client {
+            Some(client) => schema_exists(client, &metadata.schema, verbose).await?,
+            // New databases are copied from template1, which has public
+            None => metadata.schema == "public",
+        };
+    if metadata_schema_exists {
+        plan.add(format!("Metadata schema '{}'", metadata.schema), ActionOutcome::Skipped, Vec::new());
     } else {
-        let sql = templates.create_role();
-        log_sql(&sql, 1);
-        client.execute(&sql, &[]).await
-            .context("Failed to create role")?;
-        report.record(format!("Role '{}'", role), ActionOutcome::Created);
-    }
-
-    // Set up schema ownership and grants
-    let sql = templates.grant_connect();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant CONNECT")?;
-    report.record("CONNECT privilege", ActionOutcome::Updated);
-
-    let sql = templates.alter_schema_owner();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to alter schema owner")?;
-    report.record("Schema ownership", ActionOutcome::Updated);
-
-    let sql = templates.grant_schema_usage();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant USAGE on schema")?;
-    report.record("USAGE on schema", ActionOutcome::Updated);
-
-    let sql = templates.grant_schema_create();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant CREATE on schema")?;
-    report.record("CREATE on schema", ActionOutcome::Updated);
-
-    let sql = templates.grant_all_tables();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant privileges on tables")?;
-    report.record("ALL on tables", ActionOutcome::Updated);
-
-    let sql = templates.grant_all_sequences();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant privileges on sequences")?;
-    report.record("ALL on sequences", ActionOutcome::Updated);
-
-    let sql = templates.grant_all_functions();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to grant privileges on functions")?;
-    report.record("ALL on functions", ActionOutcome::Updated);
-
-    let sql = templates.alter_default_privileges_tables();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to alter default privileges for tables")?;
-    report.record("Default privileges for tables", ActionOutcome::Updated);
-
-    let sql = templates.alter_default_privileges_sequences();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to alter default privileges for sequences")?;
-    report.record("Default privileges for sequences", ActionOutcome::Updated);
-
-    let sql = templates.alter_default_privileges_functions();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to alter default privileges for functions")?;
-    report.record("Default privileges for functions", ActionOutcome::Updated);
-
-    // Create config table
-    let sql = templates.create_config_table();
-    log_sql(sql, 1);
-    client.execute(sql, &[]).await
-        .context("Failed to create config table")?;
-    report.record("Config table", ActionOutcome::Created);
-
-    // Create trigger function (only log at verbosity level 2+)
-    let sql = templates.create_trigger_function();
-    log_sql(sql, 2);
-    client.execute(sql, &[]).await
-        .context("Failed to create trigger function")?;
-    report.record("Trigger function", ActionOutcome::Updated);
-
-    // Create event trigger if it doesn't exist
-    if event_trigger_exists(&client, "auto_transfer_schema_ownership_trigger", verbose).await? {
-        report.record("Event trigger", ActionOutcome::Skipped);
+        plan.add(format!("Metadata schema '{}'", metadata.schema), ActionOutcome::Created, vec![templates.create_metadata_schema()]);
+    }
+
+    add_installation_actions(&mut plan, client.as_ref(), &templates, installed.as_ref(), verbose).await?;
+
+    if existing_mapping.is_some() {
+        plan.add("Initial mapping", ActionOutcome::Skipped, Vec::new());
     } else {
-        let sql = templates.create_event_trigger();
-        log_sql(sql, 1);
-        client.execute(sql, &[]).await
-            .context("Failed to create event trigger")?;
-        report.record("Event trigger", ActionOutcome::Created);
+        plan.add("Initial mapping", ActionOutcome::Created, vec![templates.insert_initial_mapping()]);
     }
 
-    // Insert initial mapping
-    let sql = templates.insert_initial_mapping();
-    log_sql(&sql, 1);
-    client.execute(&sql, &[]).await
-        .context("Failed to insert initial mapping")?;
-    report.record("Initial mapping", ActionOutcome::Updated);
+    if orphaned_mapping {
+        plan.add(
+            format!("Orphaned mapping for {} '{}' reactivated", schema_label.to_lowercase(), schema),
+            ActionOutcome::Updated,
+            vec![templates.reactivate_mapping()],
+        );
+    }
+
+    if let Some(preset) = &preset {
+        let recorded = existing_mapping.as_ref().is_some_and(|existing| existing.preset.as_ref() == Some(preset));
+        if recorded {
+            plan.add("Preset roles in mapping", ActionOutcome::Skipped, Vec::new());
+        } else {
+            plan.add("Preset roles in mapping", ActionOutcome::Updated, vec![templates.record_preset_roles(preset)]);
+        }
+    }
+
+    if !grantors.is_empty() {
+        let recorded = existing_mapping
+            .as_ref()
+            .and_then(|existing| existing.default_privilege_grantors.as_ref())
+            .is_some_and(|recorded| grantors.iter().all(|g| recorded.contains(g)));
+        if recorded {
+            plan.add("Default privilege grantors in mapping", ActionOutcome::Skipped, Vec::new());
+        } else {
+            plan.add(
+                "Default privilege grantors in mapping",
+                ActionOutcome::Updated,
+                templates.record_default_privilege_grantors(&grantors),
+            );
+        }
+    }
+
+    if let Some(sql) = templates.update_mapping_options() {
+        // A new row starts with the column defaults
+        let (report, on_missing_role) = existing_mapping
+            .as_ref()
+            .map_or((false, MissingRolePolicy::DEFAULT.as_str()), |existing| (existing.report_extension_owner, existing.on_missing_role.as_str()));
+        let changed = templates.options.report_extension_owner.is_some_and(|t| t != report)
+            || templates.options.on_missing_role.is_some_and(|p| p.as_str() != on_missing_role);
+        if changed {
+            plan.add("Mapping options", ActionOutcome::Updated, vec![sql]);
+        } else {
+            plan.add("Mapping options", ActionOutcome::Skipped, Vec::new());
+        }
+    }
+
+    if dry_run {
+        plan.print(verbose);
+        println!("Dry run: no changes made.");
+        return Ok(());
+    }
+
+    let mut report = ActionReport::new("Init");
+
+    // CREATE DATABASE runs from the postgres database; the rest in the target
+    let (server_actions, database_actions) = plan.actions().split_at(1);
+    apply(&server_client, server_actions, &mut report, verbose).await?;
+    drop(server_client);
+    let mut client = match client {
+        Some(client) => client,
+        None => {
+            let mut target_config = conn_opts.clone();
+            target_config.dbname = Some(database.clone());
+            connect(&target_config).await?
+        }
+    };
+    apply_in_transaction(&mut client, database_actions, &mut report, verbose).await?;
 
     report.print_summary();
 
+    if schema_is_pattern {
+        let matched: Vec<String> = pattern_matches(&client, &metadata, verbose).await?
+            .into_iter()
+            .filter(|(pattern, _)| *pattern == schema)
+            .map(|(_, schema)| schema)
+            .collect();
+        if matched.is_empty() {
+            println!("No existing schemas match '{}' yet.", schema);
+        } else {
+            println!("Existing schemas matching '{}': {}", schema, matched.join(", "));
+            println!("New objects in them are transferred to '{}'; existing objects keep their owners.", role);
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the config table, trigger functions and event triggers to the plan,
+/// moving an installation found at another location first. Shared with
+/// `init-template`.
+pub async fn add_installation_actions(
+    plan: &mut Plan,
+    client: Option<&Client>,
+    templates: &SqlTemplates,
+    installed: Option<&MetadataNames>,
+    verbose: u8,
+) -> Result<()> {
+    let metadata = &templates.metadata;
+
+    // Move an existing installation to the requested location
+    let migrating_from = installed.filter(|existing| *existing != metadata);
+    if let Some(existing) = migrating_from {
+        // The old trigger reads the config table, so it must go before the table moves
+        let mut statements = templates.drop_event_triggers(existing);
+        statements.extend(templates.migrate_config_table(existing));
+        statements.extend(templates.drop_trigger_functions(existing));
+        plan.add(
+            format!("Metadata moved from {} to {}", existing.qualified_config_table(), metadata.qualified_config_table()),
+            ActionOutcome::Updated,
+            statements,
+        );
+    }
+
+    let mut statements = vec![templates.create_config_table()];
+    statements.extend(templates.upgrade_config_table());
+    statements.push(templates.comment_config_table());
+    let outcome = if installed.is_some() { ActionOutcome::Updated } else { ActionOutcome::Created };
+    plan.add("Config table", outcome, statements);
+
+    plan.add(
+        "Trigger function",
+        ActionOutcome::Updated,
+        vec![templates.create_trigger_function(), templates.comment_trigger_function()],
+    );
+
+    // Event triggers of an installation being moved are dropped above
+    let mut existing_triggers = Vec::new();
+    if let Some(client) = client {
+        for name in [&metadata.event_trigger, &metadata.cleanup_event_trigger] {
+            let dropped = migrating_from
+                .is_some_and(|existing| existing.event_trigger == *name || existing.cleanup_event_trigger == *name);
+            if !dropped && event_trigger_exists(client, name, verbose).await? {
+                existing_triggers.push(name.clone());
+            }
+        }
+    }
+
+    if existing_triggers.contains(&metadata.event_trigger) {
+        plan.add("Event trigger", ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add("Event trigger", ActionOutcome::Created, vec![templates.create_event_trigger()]);
+    }
+
+    plan.add(
+        "Cleanup function",
+        ActionOutcome::Updated,
+        vec![templates.create_cleanup_function(), templates.comment_cleanup_function()],
+    );
+
+    if existing_triggers.contains(&metadata.cleanup_event_trigger) {
+        plan.add("Cleanup event trigger", ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add("Cleanup event trigger", ActionOutcome::Created, vec![templates.create_cleanup_event_trigger()]);
+    }
+    Ok(())
+}
+
+/// Creates the preset roles with their grants, default privileges and, for the
+/// migrator, membership in the target role.
+async fn add_preset_actions(
+    plan: &mut Plan,
+    server_client: &Client,
+    templates: &SqlTemplates,
+    preset: &PresetRoles,
+    grantors: &[String],
+    role_members: &[String],
+    verbose: u8,
+) -> Result<()> {
+    for name in [&preset.migrator, &preset.runtime, &preset.readonly] {
+        let role_templates = SqlTemplates::new(
+            templates.database.clone(),
+            templates.schema.clone(),
+            name.clone(),
+            templates.metadata.clone(),
+            templates.capabilities,
+        );
+        if role_exists(server_client, name, verbose).await? {
+            plan.add(format!("Role '{}'", name), ActionOutcome::Skipped, Vec::new());
+            if connect_granted(server_client, &templates.database, name, verbose).await? {
+                plan.add(format!("CONNECT privilege for '{}'", name), ActionOutcome::Skipped, Vec::new());
+                continue;
+            }
+        } else {
+            plan.add(format!("Role '{}'", name), ActionOutcome::Created, vec![role_templates.create_role()]);
+        }
+        plan.add(format!("CONNECT privilege for '{}'", name), ActionOutcome::Updated, vec![role_templates.grant_connect()]);
+    }
+
+    if role_members.contains(&preset.migrator) {
+        plan.add(format!("Membership of '{}' in '{}'", preset.migrator, templates.role), ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add(
+            format!("Membership of '{}' in '{}' (SET ROLE only)", preset.migrator, templates.role),
+            ActionOutcome::Created,
+            templates.grant_migrator_membership(&preset.migrator),
+        );
+    }
+
+    // Like the owner's grants, these cover whatever objects exist and are always re-issued
+    for (name, access) in [(&preset.runtime, SchemaAccess::ReadWrite), (&preset.readonly, SchemaAccess::ReadOnly)] {
+        plan.add(format!("Schema access for '{}'", name), ActionOutcome::Updated, templates.grant_schema_access(name, access));
+        let mut statements = templates.default_schema_access(None, name, access);
+        for grantor in grantors {
+            statements.extend(templates.default_schema_access(Some(grantor), name, access));
+        }
+        plan.add(format!("Default privileges for '{}'", name), ActionOutcome::Updated, statements);
+    }
+    Ok(())
+}
+
+/// Runs each action's statements in order and records it once they succeed.
+pub async fn apply(client: &Client, actions: &[PlannedAction], report: &mut ActionReport, verbose: u8) -> Result<()> {
+    for action in actions {
+        for sql in &action.statements {
+            // Function bodies only at -vv
+            if verbose >= if is_function_body(sql) { 2 } else { 1 } {
+                println!("[SQL] {}", sql);
+            }
+            client.batch_execute(sql).await
+                .with_context(|| format!("Failed to apply: {}", action.description))?;
+        }
+        report.record(action.description.clone(), action.outcome.clone());
+    }
+    Ok(())
+}
+
+/// Runs the actions in one transaction and records them once it commits, so a
+/// failure (e.g. halfway through moving the metadata) leaves the database as it was.
+pub async fn apply_in_transaction(client: &mut Client, actions: &[PlannedAction], report: &mut ActionReport, verbose: u8) -> Result<()> {
+    let transaction = client.transaction().await
+        .context("Failed to start transaction")?;
+    for action in actions {
+        for sql in &action.statements {
+            // Function bodies only at -vv
+            if verbose >= if is_function_body(sql) { 2 } else { 1 } {
+                println!("[SQL] {}", sql);
+            }
+            transaction.batch_execute(sql).await
+                .with_context(|| format!("Failed to apply: {}. Nothing was changed in the database.", action.description))?;
+        }
+    }
+    transaction.commit().await
+        .context("Failed to commit")?;
+
+    for action in actions {
+        report.record(action.description.clone(), action.outcome.clone());
+    }
     Ok(())
 }
 
@@ -191,7 +588,7 @@ async fn database_exists(client: &Client, database: &str, verbose: u8) -> Result
     Ok(row.is_ok())
 }
 
-async fn schema_exists(client: &Client, schema: &str, verbose: u8) -> Result<bool> {
+pub async fn schema_exists(client: &Client, schema: &str, verbose: u8) -> Result<bool> {
     let sql = "SELECT 1 FROM pg_namespace WHERE nspname = $1";
     if verbose >= 1 {
         println!("[SQL] {} -- params: [{}]", sql, schema);
@@ -224,16 +621,100 @@ async fn event_trigger_exists(client: &Client, trigger_name: &str, verbose: u8)
     Ok(row.is_ok())
 }
 
-async fn get_schema_mapping(client: &Client, schema: &str, verbose: u8) -> Result<Option<String>> {
-    let sql = "SELECT target_role FROM public.schema_ownership_config WHERE schema_name = $1";
+/// Whether `role` holds an explicit CONNECT grant on `database`, as opposed to
+/// one through PUBLIC.
+async fn connect_granted(client: &Client, database: &str, role: &str, verbose: u8) -> Result<bool> {
+    let sql = "
+        SELECT EXISTS (
+            SELECT 1
+            FROM pg_database d
+            CROSS JOIN LATERAL aclexplode(d.datacl) a
+            JOIN pg_roles r ON r.oid = a.grantee
+            WHERE d.datname = $1 AND r.rolname = $2 AND a.privilege_type = 'CONNECT'
+        )
+    ";
     if verbose >= 1 {
-        println!("[SQL] {} -- params: [{}]", sql, schema);
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), database, role);
     }
-    match client.query_one(sql, &[&schema]).await {
-        Ok(row) => {
-            let role: String = row.get(0);
-            Ok(Some(role))
-        }
-        Err(_) => Ok(None),
+    let row = client.query_one(sql, &[&database, &role])
+        .await
+        .context("Failed to check CONNECT privilege")?;
+    Ok(row.get(0))
+}
+
+/// Owner of an existing schema and the USAGE and CREATE grants `role` holds on it.
+struct SchemaState {
+    owner: String,
+    has_usage: bool,
+    has_create: bool,
+}
+
+async fn schema_state(client: &Client, schema: &str, role: &str, verbose: u8) -> Result<Option<SchemaState>> {
+    let sql = "
+        SELECT pg_get_userbyid(n.nspowner)::text,
+               EXISTS (SELECT 1 FROM aclexplode(n.nspacl) a JOIN pg_roles r ON r.oid = a.grantee
+                       WHERE r.rolname = $2 AND a.privilege_type = 'USAGE'),
+               EXISTS (SELECT 1 FROM aclexplode(n.nspacl) a JOIN pg_roles r ON r.oid = a.grantee
+                       WHERE r.rolname = $2 AND a.privilege_type = 'CREATE')
+        FROM pg_namespace n
+        WHERE n.nspname = $1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), schema, role);
+    }
+    let row = client.query_opt(sql, &[&schema, &role])
+        .await
+        .context("Failed to look up schema")?;
+    Ok(row.map(|row| SchemaState {
+        owner: row.get(0),
+        has_usage: row.get(1),
+        has_create: row.get(2),
+    }))
+}
+
+/// A config table row for the schema (or pattern) being initialized.
+struct ExistingMapping {
+    role: String,
+    active: bool,
+    is_pattern: bool,
+    report_extension_owner: bool,
+    on_missing_role: String,
+    preset: Option<PresetRoles>,
+    default_privilege_grantors: Option<Vec<String>>,
+}
+
+/// Columns the next config table upgrade adds or renames are read with the values they will get.
+async fn get_schema_mapping(client: &Client, metadata: &MetadataNames, schema: &str, verbose: u8) -> Result<Option<ExistingMapping>> {
+    let sql = format!(
+        "SELECT c.target_role::text, COALESCE((to_jsonb(c) ->> 'active')::boolean, true),
+                COALESCE((to_jsonb(c) ->> 'is_pattern')::boolean, false),
+                COALESCE((to_jsonb(c) ->> 'report_extension_owner')::boolean,
+                         (to_jsonb(c) ->> 'transfer_extension_owner')::boolean, false),
+                COALESCE(to_jsonb(c) ->> 'on_missing_role', {default_policy}),
+                to_jsonb(c) ->> 'migrator_role', to_jsonb(c) ->> 'runtime_role', to_jsonb(c) ->> 'readonly_role',
+                CASE WHEN jsonb_typeof(to_jsonb(c) -> 'default_privilege_grantors') = 'array'
+                     THEN ARRAY(SELECT jsonb_array_elements_text(to_jsonb(c) -> 'default_privilege_grantors'))
+                END
+         FROM {} c WHERE c.schema_name = $1",
+        metadata.qualified_config_table(),
+        default_policy = quote_literal(MissingRolePolicy::DEFAULT.as_str())
+    );
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, schema);
     }
+    let row = client.query_opt(&sql, &[&schema])
+        .await
+        .context("Failed to read existing schema mapping")?;
+    Ok(row.map(|row| ExistingMapping {
+        role: row.get(0),
+        active: row.get(1),
+        is_pattern: row.get(2),
+        report_extension_owner: row.get(3),
+        on_missing_role: row.get(4),
+        preset: match (row.get(5), row.get(6), row.get(7)) {
+            (Some(migrator), Some(runtime), Some(readonly)) => Some(PresetRoles { migrator, runtime, readonly }),
+            _ => None,
+        },
+        default_privilege_grantors: row.get(8),
+    }))
 }
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{discover_metadata, BLOCKED_DATABASES};
+use crate::commands::init::{add_installation_actions, apply, apply_in_transaction, schema_exists, MetadataOptions};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::{ActionOutcome, ActionReport, Plan};
+use crate::sql_templates::{MetadataNames, SqlTemplates};
+
+/// The template database `init-template` prepares.
+#[derive(Debug)]
+pub struct InitTemplateOptions {
+    pub template: String,
+    pub metadata: MetadataOptions,
+    pub migrate_metadata: bool,
+    /// Print the plan without making any changes
+    pub dry_run: bool,
+}
+
+/// Installs the config table, trigger functions and event triggers in a
+/// template database, creating it if needed, so every database copied from it
+/// starts with the pattern in place. Mappings are added per database with `init`.
+pub async fn execute(conn_opts: ConnectionConfig, opts: InitTemplateOptions, verbose: u8) -> Result<()> {
+    let template = opts.template;
+    if BLOCKED_DATABASES.contains(&template.as_str()) {
+        anyhow::bail!(
+            "Cannot install into system database '{}'. Create a custom template (e.g. tmpl_app) and use it with CREATE DATABASE ... TEMPLATE.",
+            template
+        );
+    }
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some("postgres".to_string());
+    let server_client = connect(&config).await?;
+    let capabilities = server_capabilities(&server_client).await?;
+
+    let mut templates = SqlTemplates::new(
+        template.clone(),
+        String::new(),
+        String::new(),
+        MetadataNames::default(),
+        capabilities,
+    );
+    let mut plan = Plan::new(if opts.dry_run { "Init Template Plan (dry run)" } else { "Init Template Plan" });
+
+    let sql = "SELECT datistemplate, datallowconn FROM pg_database WHERE datname = $1";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, template);
+    }
+    let state: Option<(bool, bool)> = server_client.query_opt(sql, &[&template])
+        .await
+        .context("Failed to look up template database")?
+        .map(|row| (row.get(0), row.get(1)));
+
+    let exists = match state {
+        None => {
+            plan.add(format!("Database '{}'", template), ActionOutcome::Created, vec![templates.create_database()]);
+            plan.add(format!("'{}' marked as template", template), ActionOutcome::Updated, vec![templates.mark_template()]);
+            false
+        }
+        Some((false, _)) => anyhow::bail!(
+            "Database '{}' exists but is not a template. Mark it with 'ALTER DATABASE {} IS_TEMPLATE true' first, or choose a new name.",
+            template, template
+        ),
+        Some((true, false)) => anyhow::bail!("Template database '{}' does not allow connections", template),
+        Some((true, true)) => {
+            plan.add(format!("Template database '{}'", template), ActionOutcome::Skipped, Vec::new());
+            true
+        }
+    };
+    let server_action_count = plan.actions().len();
+
+    let client = if exists {
+        let mut target_config = conn_opts.clone();
+        target_config.dbname = Some(template.clone());
+        Some(connect(&target_config).await?)
+    } else {
+        None
+    };
+
+    if !exists && let Some(sql) = templates.revoke_public_schema_create() {
+        plan.add("CREATE on public schema revoked from PUBLIC", ActionOutcome::Updated, vec![sql]);
+    }
+
+    let installed = match &client {
+        Some(client) => discover_metadata(client, verbose).await?,
+        None => None,
+    };
+    let metadata = opts.metadata.resolve(installed.as_ref().unwrap_or(&MetadataNames::default()));
+    if let Some(existing) = &installed
+        && *existing != metadata
+        && !opts.migrate_metadata
+    {
+        anyhow::bail!(
+            "Template '{}' already has the config table at {}. Re-run with --migrate-metadata to move the installation to {}.",
+            template,
+            existing.qualified_config_table(),
+            metadata.qualified_config_table()
+        );
+    }
+    templates.metadata = metadata.clone();
+
+    let metadata_schema_exists = match &client {
+        Some(client) => schema_exists(client, &metadata.schema, verbose).await?,
+        None => metadata.schema == "public",
+    };
+    if metadata_schema_exists {
+        plan.add(format!("Metadata schema '{}'", metadata.schema), ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add(format!("Metadata schema '{}'", metadata.schema), ActionOutcome::Created, vec![templates.create_metadata_schema()]);
+    }
+
+    add_installation_actions(&mut plan, client.as_ref(), &templates, installed.as_ref(), verbose).await?;
+
+    if opts.dry_run {
+        plan.print(verbose);
+        println!("Dry run: no changes made.");
+        return Ok(());
+    }
+
+    let mut report = ActionReport::new("Init Template");
+
+    // CREATE DATABASE and IS_TEMPLATE run from the postgres database; the rest in the template
+    let (server_actions, template_actions) = plan.actions().split_at(server_action_count);
+    apply(&server_client, server_actions, &mut report, verbose).await?;
+    drop(server_client);
+    let mut client = match client {
+        Some(client) => client,
+        None => {
+            let mut target_config = conn_opts.clone();
+            target_config.dbname = Some(template.clone());
+            connect(&target_config).await?
+        }
+    };
+    apply_in_transaction(&mut client, template_actions, &mut report, verbose).await?;
+    // CREATE DATABASE ... TEMPLATE fails while anyone is connected to the template
+    drop(client);
+
+    report.print_summary();
+    println!(
+        "Databases created with 'CREATE DATABASE ... TEMPLATE {}' start with the pattern installed; run 'init' in them to add mappings.",
+        template
+    );
+    Ok(())
+}
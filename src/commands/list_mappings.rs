@@ -1,47 +1,31 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use tokio_postgres::types::ToSql;
 
+use crate::catalog::{discover_metadata, load_mappings, pattern_matches, template_databases, user_databases, Mapping};
 use crate::db::{connect, ConnectionConfig};
-
-fn truncate_with_ellipsis(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}[...]", &s[..max_len.saturating_sub(5)])
-    }
-}
+use crate::report::truncate_with_ellipsis;
 
 #[derive(Debug)]
 struct MappingRow {
     database: String,
-    schema_name: String,
-    target_role: String,
+    mapping: Mapping,
     granted_to: Vec<String>,
-    created_at: chrono::DateTime<chrono::Utc>,
-    updated_at: chrono::DateTime<chrono::Utc>,
+    /// Status label and explanation for mappings that are not fully working
+    problem: Option<(&'static str, String)>,
+    /// Schemas currently governed by this mapping, for pattern mappings
+    matched_schemas: Option<Vec<String>>,
 }
 
-pub async fn execute(conn_opts: ConnectionConfig, verbose: u8) -> Result<()> {
+pub async fn execute(conn_opts: ConnectionConfig, include_templates: bool, verbose: u8) -> Result<()> {
     // Connect to postgres system database to get list of all databases
     let mut config = conn_opts.clone();
     config.dbname = Some("postgres".to_string());
     let client = connect(&config).await?;
 
-    // Query for all non-system databases
-    // Blocked: PostgreSQL core + cloud provider (AWS RDS, Azure, GCP) system databases
-    let blocked_databases = ["postgres", "template0", "template1", "rdsadmin", "azure_maintenance", "cloudsqladmin"];
-    let sql = "SELECT datname FROM pg_database WHERE datistemplate = false ORDER BY datname";
-    if verbose >= 1 {
-        println!("[SQL] {}", sql);
-    }
-    let db_rows = client.query(sql, &[])
-        .await
-        .context("Failed to query pg_database")?;
-
-    let databases: Vec<String> = db_rows
-        .iter()
-        .map(|row| row.get(0))
-        .filter(|dbname: &String| !blocked_databases.contains(&dbname.as_str()))
-        .collect();
+    // Query for all non-system databases, and templates on request
+    let mut databases = user_databases(&client, verbose).await?;
+    let template_names = if include_templates { template_databases(&client, verbose).await? } else { Vec::new() };
+    databases.extend(template_names.iter().cloned());
 
     if databases.is_empty() {
         println!("No non-system databases found.");
@@ -51,6 +35,8 @@ pub async fn execute(conn_opts: ConnectionConfig, verbose: u8) -> Result<()> {
     // Query each database for schema_ownership_config mappings
     let mut all_mappings = Vec::new();
     let mut databases_with_mappings: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // Templates with an installation, and where its config table is
+    let mut installed_templates = Vec::new();
 
     for database in &databases {
         let mut db_config = conn_opts.clone();
@@ -66,24 +52,15 @@ pub async fn execute(conn_opts: ConnectionConfig, verbose: u8) -> Result<()> {
             }
         };
 
-        let sql = "SELECT schema_name, target_role, created_at, updated_at FROM public.schema_ownership_config ORDER BY schema_name";
-        if verbose >= 1 {
-            println!("[SQL] {} (database: {})", sql, database);
-        }
-
-        let rows = match db_client.query(sql, &[]).await {
-            Ok(rows) => rows,
-            Err(e) => {
-                // Check if the error is because the table doesn't exist
-                if let Some(db_err) = e.as_db_error() {
-                    if db_err.code().code() == "42P01" {
-                        // SQLSTATE 42P01: undefined_table - skip this database
-                        if verbose >= 1 {
-                            println!("  No schema_ownership_config in database '{}'", database);
-                        }
-                        continue;
-                    }
+        let metadata = match discover_metadata(&db_client, verbose).await {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => {
+                if verbose >= 1 {
+                    println!("  No schema_ownership_config in database '{}'", database);
                 }
+                continue;
+            }
+            Err(e) => {
                 if verbose >= 1 {
                     println!("Warning: Failed to query database '{}': {}", database, e);
                 }
@@ -91,44 +68,132 @@ pub async fn execute(conn_opts: ConnectionConfig, verbose: u8) -> Result<()> {
             }
         };
 
-        if !rows.is_empty() {
+        let mappings = match load_mappings(&db_client, &metadata, verbose).await {
+            Ok(mappings) => mappings,
+            Err(e) => {
+                if verbose >= 1 {
+                    println!("Warning: Failed to query database '{}': {:#}", database, e);
+                }
+                continue;
+            }
+        };
+
+        if !mappings.is_empty() {
             databases_with_mappings.insert(database.clone());
         }
+        let is_template = template_names.contains(database);
+        if is_template {
+            installed_templates.push((database.clone(), metadata.qualified_config_table(), mappings.len()));
+        }
 
-        for row in rows {
-            let target_role: String = row.get(1);
+        // Grants made by grant-member and create-login are in the history table,
+        // which only exists once one of the commands that write it has run
+        let history_table = metadata.qualified_history_table();
+        let has_history = match db_client.query_one("SELECT to_regclass($1) IS NOT NULL", &[&history_table]).await {
+            Ok(row) => row.get::<_, bool>(0),
+            Err(_) => false,
+        };
+        let latest_grant = if has_history {
+            format!(
+                "SELECT h.action, h.changed_by::text, h.changed_at
+                 FROM {} h
+                 WHERE h.schema_name = $2 AND h.new_role = $1 AND h.member = r.rolname
+                   AND h.action IN ('grant-member', 'create-login', 'revoke-member')
+                 ORDER BY h.changed_at DESC, h.id DESC
+                 LIMIT 1",
+                history_table
+            )
+        } else {
+            "SELECT NULL::text AS action, NULL::text AS changed_by, NULL::timestamptz AS changed_at".to_string()
+        };
 
-            // Query for roles/users that have been granted this target role
-            let members_sql = "
-                SELECT r.rolname
-                FROM pg_roles r
-                JOIN pg_auth_members m ON m.member = r.oid
-                JOIN pg_roles g ON m.roleid = g.oid
-                WHERE g.rolname = $1
+        // Only config tables that hold a pattern have the is_pattern column
+        let matches = if mappings.iter().any(|m| m.is_pattern) {
+            match pattern_matches(&db_client, &metadata, verbose).await {
+                Ok(matches) => matches,
+                Err(e) => {
+                    if verbose >= 1 {
+                        println!("Warning: Failed to resolve pattern mappings in '{}': {}", database, e);
+                    }
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        for mapping in mappings {
+            // Query for roles/users that have been granted this target role, noting
+            // options that differ from a plain GRANT (INHERIT and SET exist from 16)
+            // and who granted it when, or that no grant of it was recorded
+            let members_sql = format!("
+                SELECT r.rolname || ' (' || array_to_string(options || CASE
+                           WHEN g.action IS NULL OR g.action = 'revoke-member' THEN 'unrecorded'
+                           ELSE 'by ' || g.changed_by || ' ' || to_char(g.changed_at, 'YYYY-MM-DD')
+                       END, ', ') || ')'
+                FROM (
+                    SELECT r.rolname::text AS rolname,
+                           array_remove(ARRAY[
+                               CASE WHEN bool_or(m.admin_option) THEN 'admin' END,
+                               CASE WHEN NOT bool_or(COALESCE((to_jsonb(m) ->> 'inherit_option')::boolean, true)) THEN 'noinherit' END,
+                               CASE WHEN NOT bool_or(COALESCE((to_jsonb(m) ->> 'set_option')::boolean, true)) THEN 'noset' END
+                           ], NULL) AS options
+                    FROM pg_roles r
+                    JOIN pg_auth_members m ON m.member = r.oid
+                    JOIN pg_roles g ON m.roleid = g.oid
+                    WHERE g.rolname = $1
+                    GROUP BY r.rolname
+                ) r
+                LEFT JOIN LATERAL ({}) g ON true
                 ORDER BY r.rolname
-            ";
+            ", latest_grant);
 
             if verbose >= 2 {
-                println!("[SQL] {} (database: {}, role: {})", members_sql.trim(), database, target_role);
+                println!("[SQL] {} (database: {}, role: {})", members_sql.trim(), database, mapping.target_role);
             }
 
-            let granted_to = match db_client.query(members_sql, &[&target_role]).await {
+            let params: &[&(dyn ToSql + Sync)] = if has_history {
+                &[&mapping.target_role, &mapping.schema_name]
+            } else {
+                &[&mapping.target_role]
+            };
+            let granted_to = match db_client.query(&members_sql, params).await {
                 Ok(member_rows) => member_rows.iter().map(|r| r.get(0)).collect(),
                 Err(e) => {
                     if verbose >= 1 {
-                        println!("Warning: Failed to query role members for '{}': {}", target_role, e);
+                        println!("Warning: Failed to query role members for '{}': {}", mapping.target_role, e);
                     }
                     Vec::new()
                 }
             };
 
+            let problem = if !mapping.active {
+                Some(("orphaned", match (&mapping.dropped_at, &mapping.dropped_by) {
+                    (Some(at), Some(by)) => format!("schema dropped {} by {}", at.format("%Y-%m-%d %H:%M:%S"), by),
+                    _ => "mapping deactivated".to_string(),
+                }))
+            } else if !mapping.schema_exists && !mapping.is_pattern {
+                Some(("orphaned", "schema no longer exists, mapping still active".to_string()))
+            } else if !mapping.role_exists {
+                Some(("no role", format!("target role no longer exists (on_missing_role: {})", mapping.on_missing_role)))
+            } else {
+                None
+            };
+
+            let matched_schemas = mapping.is_pattern.then(|| {
+                matches
+                    .iter()
+                    .filter(|(pattern, _)| *pattern == mapping.schema_name)
+                    .map(|(_, schema)| schema.clone())
+                    .collect()
+            });
+
             all_mappings.push(MappingRow {
-                database: database.clone(),
-                schema_name: row.get(0),
-                target_role,
+                database: if is_template { format!("{} (template)", database) } else { database.clone() },
+                mapping,
                 granted_to,
-                created_at: row.get(2),
-                updated_at: row.get(3),
+                problem,
+                matched_schemas,
             });
         }
     }
@@ -136,35 +201,115 @@ pub async fn execute(conn_opts: ConnectionConfig, verbose: u8) -> Result<()> {
     if all_mappings.is_empty() {
         println!("No schema-to-role mappings found in any database.");
         println!("Run 'init' command to set up the pattern in a database.");
+        print_templates(&installed_templates);
         return Ok(());
     }
 
     // Display results
-    println!("{:<20} {:<20} {:<30} {:<30} {:<21} {:<19}", "Database", "Schema", "Target Role", "Granted To", "Created At", "Updated At");
-    println!("{}", "-".repeat(144));
+    println!("{:<20} {:<20} {:<30} {:<30} {:<21} {:<21} {:<8}", "Database", "Schema", "Target Role", "Granted To", "Created At", "Updated At", "Status");
+    println!("{}", "-".repeat(156));
 
-    for mapping in &all_mappings {
-        let truncated_role = truncate_with_ellipsis(&mapping.target_role, 30);
-        let granted_display = if mapping.granted_to.is_empty() {
+    for row in &all_mappings {
+        let truncated_role = truncate_with_ellipsis(&row.mapping.target_role, 30);
+        let granted_display = if row.granted_to.is_empty() {
             "(none)".to_string()
         } else {
-            mapping.granted_to.join(", ")
+            row.granted_to.join(", ")
         };
         let truncated_granted = truncate_with_ellipsis(&granted_display, 30);
 
+        let status = row.problem.as_ref().map_or(
+            if row.matched_schemas.is_some() { "pattern" } else { "active" },
+            |(status, _)| status,
+        );
+
         println!(
-            "{:<20} {:<20} {:<30} {:<30} {:<21} {:<19}",
-            mapping.database,
-            mapping.schema_name,
+            "{:<20} {:<20} {:<30} {:<30} {:<21} {:<21} {:<8}",
+            row.database,
+            truncate_with_ellipsis(&row.mapping.schema_name, 20),
             truncated_role,
             truncated_granted,
-            mapping.created_at.format("%Y-%m-%d %H:%M:%S"),
-            mapping.updated_at.format("%Y-%m-%d %H:%M:%S")
+            format_timestamp(row.mapping.created_at),
+            format_timestamp(row.mapping.updated_at),
+            status
         );
     }
 
+    let patterns: Vec<&MappingRow> = all_mappings.iter().filter(|m| m.matched_schemas.is_some()).collect();
+    if !patterns.is_empty() {
+        println!();
+        println!("Pattern mappings (exact mappings take precedence):");
+        for row in &patterns {
+            let matched = row.matched_schemas.as_deref().unwrap_or_default();
+            let matched_display = if matched.is_empty() {
+                "(no matching schemas)".to_string()
+            } else {
+                matched.join(", ")
+            };
+            println!("  {}.{} -> {}: {}", row.database, row.mapping.schema_name, row.mapping.target_role, matched_display);
+        }
+    }
+
+    // The table column is truncated; grantors and dates are listed in full here
+    let with_members: Vec<&MappingRow> = all_mappings.iter().filter(|m| !m.granted_to.is_empty()).collect();
+    if !with_members.is_empty() {
+        println!();
+        println!("Members (who granted them and when, from the history table):");
+        for row in &with_members {
+            println!(
+                "  {}.{} -> {}: {}",
+                row.database, row.mapping.schema_name, row.mapping.target_role, row.granted_to.join(", ")
+            );
+        }
+    }
+
+    let with_presets: Vec<&MappingRow> = all_mappings.iter().filter(|m| m.mapping.preset.is_some()).collect();
+    if !with_presets.is_empty() {
+        println!();
+        println!("Preset roles:");
+        for row in &with_presets {
+            if let Some(preset) = &row.mapping.preset {
+                println!(
+                    "  {}.{} -> {}: migrator {}, runtime {}, read-only {}",
+                    row.database, row.mapping.schema_name, row.mapping.target_role, preset.migrator, preset.runtime, preset.readonly
+                );
+            }
+        }
+    }
+
+    let needing_attention: Vec<&MappingRow> = all_mappings.iter().filter(|m| m.problem.is_some()).collect();
+    if !needing_attention.is_empty() {
+        println!();
+        println!("Mappings needing attention (recreate the role, or re-run 'init' to take over the schema name):");
+        for row in &needing_attention {
+            if let Some((_, reason)) = &row.problem {
+                println!("  {}.{} -> {}: {}", row.database, row.mapping.schema_name, row.mapping.target_role, reason);
+            }
+        }
+    }
+
+    print_templates(&installed_templates);
+
     println!();
     println!("Total mappings: {} across {} database(s)", all_mappings.len(), databases_with_mappings.len());
 
     Ok(())
 }
+
+/// The timestamp columns have defaults but are nullable.
+fn format_timestamp(timestamp: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    timestamp.map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Lists the template databases that carry an installation; their mappings
+/// (usually none) are in the table above.
+fn print_templates(installed_templates: &[(String, String, usize)]) {
+    if installed_templates.is_empty() {
+        return;
+    }
+    println!();
+    println!("Template databases with the pattern installed (copied by CREATE DATABASE ... TEMPLATE):");
+    for (template, config_table, mapping_count) in installed_templates {
+        println!("  {}: config table {}, {} mapping(s)", template, config_table, mapping_count);
+    }
+}
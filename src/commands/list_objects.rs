@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::catalog::{discover_metadata, managed_schemas, owned_objects, user_databases};
+use crate::db::{connect, ConnectionConfig};
+use crate::report::truncate_with_ellipsis;
+
+/// Narrows which databases and schemas `list-objects` covers, and how it prints them.
+#[derive(Debug, Default)]
+pub struct ListObjectsOptions {
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ObjectEntry {
+    database: String,
+    schema: String,
+    #[serde(rename = "type")]
+    object_type: String,
+    identity: String,
+    owner: String,
+    expected_owner: String,
+    owner_matches: bool,
+    /// Bytes, for tables, materialized views and sequences
+    size: Option<i64>,
+    /// Raw `aclitem` entries; `null` means default privileges
+    acl: Option<Vec<String>>,
+}
+
+/// Lists every object in the managed schemas with its owner, size and
+/// privileges, walking the databases the way `list-mappings` does.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ListObjectsOptions, verbose: u8) -> Result<()> {
+    let databases = match &opts.database {
+        Some(database) => vec![database.clone()],
+        None => {
+            let mut config = conn_opts.clone();
+            config.dbname = Some("postgres".to_string());
+            let client = connect(&config).await?;
+            user_databases(&client, verbose).await?
+        }
+    };
+
+    let mut entries = Vec::new();
+    let mut schema_count = 0;
+
+    for database in &databases {
+        let mut config = conn_opts.clone();
+        config.dbname = Some(database.clone());
+        let client = match connect(&config).await {
+            Ok(client) => client,
+            Err(e) if opts.database.is_none() => {
+                eprintln!("Warning: Failed to connect to database '{}': {}", database, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let Some(metadata) = discover_metadata(&client, verbose).await? else {
+            if opts.database.is_some() {
+                anyhow::bail!("Database '{}' has no schema_ownership_config. Run 'init' first.", database);
+            }
+            continue;
+        };
+
+        let managed: Vec<_> = managed_schemas(&client, &metadata, verbose).await?
+            .into_iter()
+            .filter(|m| opts.schema.as_ref().is_none_or(|s| *s == m.schema))
+            .collect();
+        if managed.is_empty() {
+            continue;
+        }
+        schema_count += managed.len();
+
+        let schemas: Vec<String> = managed.iter().map(|m| m.schema.clone()).collect();
+        for object in owned_objects(&client, &schemas, true, verbose).await? {
+            let Some(expected) = managed.iter().find(|m| m.schema == object.schema) else {
+                continue;
+            };
+            entries.push(ObjectEntry {
+                database: database.clone(),
+                schema: object.schema,
+                object_type: object.object_type,
+                identity: object.identity,
+                owner_matches: object.owner == expected.target_role,
+                owner: object.owner,
+                expected_owner: expected.target_role.clone(),
+                size: object.size,
+                acl: object.acl,
+            });
+        }
+    }
+
+    if let Some(schema) = &opts.schema
+        && schema_count == 0
+    {
+        anyhow::bail!("Schema '{}' is not managed by any mapping", schema);
+    }
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&entries).context("Failed to render JSON")?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No managed schemas found.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<20} {:<20} {:<45} {:<20} {:<5} {:>10}  Privileges",
+        "Database", "Schema", "Type", "Object", "Owner", "Match", "Size"
+    );
+    println!("{}", "-".repeat(170));
+    for entry in &entries {
+        println!(
+            "{:<20} {:<20} {:<20} {:<45} {:<20} {:<5} {:>10}  {}",
+            truncate_with_ellipsis(&entry.database, 20),
+            truncate_with_ellipsis(&entry.schema, 20),
+            truncate_with_ellipsis(&entry.object_type, 20),
+            truncate_with_ellipsis(&entry.identity, 45),
+            truncate_with_ellipsis(&entry.owner, 20),
+            if entry.owner_matches { "yes" } else { "NO" },
+            entry.size.map(format_size).unwrap_or_default(),
+            acl_summary(entry.acl.as_deref())
+        );
+    }
+
+    let mismatched = entries.iter().filter(|e| !e.owner_matches).count();
+    println!();
+    println!(
+        "{} object(s) in {} managed schema(s); {} not owned by their target role.",
+        entries.len(), schema_count, mismatched
+    );
+    Ok(())
+}
+
+/// Size in the units `pg_size_pretty` uses.
+fn format_size(bytes: i64) -> String {
+    let mut value = bytes;
+    for unit in ["bytes", "kB", "MB", "GB"] {
+        if value.abs() < 10 * 1024 {
+            return format!("{} {}", value, unit);
+        }
+        value /= 1024;
+    }
+    format!("{} TB", value)
+}
+
+/// `grantee=privileges` for each entry, without the grantor; an empty grantee is PUBLIC.
+fn acl_summary(acl: Option<&[String]>) -> String {
+    let Some(acl) = acl else {
+        return "(default)".to_string();
+    };
+    if acl.is_empty() {
+        return "(none)".to_string();
+    }
+    acl.iter()
+        .map(|item| {
+            let item = item.split_once('/').map_or(item.as_str(), |(grant, _)| grant);
+            match item.strip_prefix('=') {
+                Some(privileges) => format!("PUBLIC={}", privileges),
+                None => item.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
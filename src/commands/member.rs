@@ -0,0 +1,295 @@
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::catalog::{default_privilege_grantors, discover_metadata, load_mappings, Mapping};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::{ActionOutcome, ActionReport, Plan};
+use crate::sql_templates::{MembershipOptions, SqlTemplates};
+
+/// Which mapping's target role `grant-member` and `revoke-member` hand out, and to whom.
+#[derive(Debug)]
+pub struct MemberOptions {
+    pub database: String,
+    /// Schema (or pattern) of the mapping whose target role is granted
+    pub schema: String,
+    pub member: String,
+    /// Ignored by `revoke-member`
+    pub membership: MembershipOptions,
+    pub dry_run: bool,
+}
+
+/// Direct membership of one role in another; on PostgreSQL 16 the options of
+/// all grantors' grants are combined.
+struct Membership {
+    admin: bool,
+    inherit: Option<bool>,
+    set: Option<bool>,
+}
+
+/// Grants a mapping's target role to a user or role, adds default privileges
+/// for objects the new member creates, and records the grant in the history table.
+pub async fn grant(conn_opts: ConnectionConfig, opts: MemberOptions, verbose: u8) -> Result<()> {
+    let (mut client, templates, mapping, mappings) = prepare(&conn_opts, &opts, verbose).await?;
+
+    let has_options = templates.capabilities.has_membership_options();
+    if !has_options && (opts.membership.inherit.is_some() || opts.membership.set.is_some()) {
+        anyhow::bail!(
+            "--inherit and --set need PostgreSQL 16 or later (server is {})",
+            templates.capabilities.major_version()
+        );
+    }
+
+    let mut plan = Plan::new(format!("Grant '{}' to '{}'", mapping.target_role, opts.member));
+    add_history_table(&client, &mut plan, &templates, verbose).await?;
+
+    let description = format!("Membership of '{}' in '{}'", opts.member, mapping.target_role);
+    let wanted = &opts.membership;
+    match membership(&client, &mapping.target_role, &opts.member, verbose).await? {
+        Some(current)
+            if wanted.admin.is_none_or(|admin| admin == current.admin)
+                && (wanted.inherit.is_none() || wanted.inherit == current.inherit)
+                && (wanted.set.is_none() || wanted.set == current.set) =>
+        {
+            plan.add(description, ActionOutcome::Skipped, Vec::new());
+        }
+        current => {
+            let mut statements = templates.grant_membership(&opts.member, wanted);
+            statements.push(templates.record_membership("grant-member", &opts.member, &wanted.describe()));
+            let outcome = if current.is_some() { ActionOutcome::Updated } else { ActionOutcome::Created };
+            let with = wanted.describe();
+            let description = if with.is_empty() { description } else { format!("{} ({})", description, with) };
+            plan.add(description, outcome, statements);
+        }
+    }
+
+    // Objects the member creates get the same default grants as init gives the
+    // role's existing members
+    for other in same_role_schemas(&mappings, &mapping.target_role) {
+        let schema_templates = templates_for(&templates, other);
+        let description = format!("Default privileges for role '{}' in schema '{}'", opts.member, other.schema_name);
+        let grantors = default_privilege_grantors(&client, &other.schema_name, &mapping.target_role, verbose).await?;
+        if grantors.iter().any(|(grantor, _)| *grantor == opts.member) {
+            plan.add(description, ActionOutcome::Skipped, Vec::new());
+        } else {
+            let mut statements = schema_templates.alter_default_privileges_for_role(&opts.member);
+            statements.extend(schema_templates.record_default_privilege_grantors(std::slice::from_ref(&opts.member)));
+            plan.add(description, ActionOutcome::Created, statements);
+        }
+    }
+
+    run(&mut client, plan, "Grant Member", opts.dry_run, verbose).await
+}
+
+/// Revokes a mapping's target role from a user or role, drops the default
+/// privileges `grant-member` added for it, and records the revoke.
+pub async fn revoke(conn_opts: ConnectionConfig, opts: MemberOptions, verbose: u8) -> Result<()> {
+    let (mut client, templates, mapping, mappings) = prepare(&conn_opts, &opts, verbose).await?;
+
+    if membership(&client, &mapping.target_role, &opts.member, verbose).await?.is_none() {
+        anyhow::bail!("Role '{}' is not a direct member of '{}'", opts.member, mapping.target_role);
+    }
+
+    let mut plan = Plan::new(format!("Revoke '{}' from '{}'", mapping.target_role, opts.member));
+    add_history_table(&client, &mut plan, &templates, verbose).await?;
+    plan.add(
+        format!("Membership of '{}' in '{}'", opts.member, mapping.target_role),
+        ActionOutcome::Removed,
+        vec![
+            templates.revoke_membership(&opts.member),
+            templates.record_membership("revoke-member", &opts.member, ""),
+        ],
+    );
+
+    // A member that keeps the role through another role still needs its defaults
+    if member_through_other_roles(&client, &mapping.target_role, &opts.member, verbose).await? {
+        println!(
+            "Note: '{}' is still a member of '{}' through another role; its default privileges are kept.",
+            opts.member, mapping.target_role
+        );
+    } else {
+        for other in same_role_schemas(&mappings, &mapping.target_role) {
+            let grantors = default_privilege_grantors(&client, &other.schema_name, &mapping.target_role, verbose).await?;
+            if grantors.iter().any(|(grantor, _)| *grantor == opts.member) {
+                let schema_templates = templates_for(&templates, other);
+                let mut statements = schema_templates.revoke_default_privileges_for_role(&opts.member);
+                statements.extend(schema_templates.forget_default_privilege_grantors(std::slice::from_ref(&opts.member)));
+                plan.add(
+                    format!("Default privileges for role '{}' in schema '{}'", opts.member, other.schema_name),
+                    ActionOutcome::Removed,
+                    statements,
+                );
+            }
+        }
+    }
+
+    run(&mut client, plan, "Revoke Member", opts.dry_run, verbose).await?;
+
+    // On PostgreSQL 16 a grant made by another grantor survives our REVOKE
+    if !opts.dry_run && membership(&client, &mapping.target_role, &opts.member, verbose).await?.is_some() {
+        println!(
+            "Warning: '{}' is still a member of '{}' through a grant made by another role (see pg_auth_members.grantor).",
+            opts.member, mapping.target_role
+        );
+    }
+    Ok(())
+}
+
+/// Connects and checks that the mapping is active and both roles exist.
+async fn prepare(conn_opts: &ConnectionConfig, opts: &MemberOptions, verbose: u8) -> Result<(Client, SqlTemplates, Mapping, Vec<Mapping>)> {
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+    let mappings = load_mappings(&client, &metadata, verbose).await?;
+    let mapping = mappings
+        .iter()
+        .find(|m| m.schema_name == opts.schema)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Schema '{}' has no mapping in database '{}'", opts.schema, opts.database))?;
+    if !mapping.active {
+        anyhow::bail!("The mapping for schema '{}' is inactive (see 'list-mappings')", opts.schema);
+    }
+    if !mapping.role_exists {
+        anyhow::bail!("Target role '{}' of schema '{}' does not exist", mapping.target_role, opts.schema);
+    }
+    if mapping.target_role == opts.member {
+        anyhow::bail!("'{}' is the target role itself", opts.member);
+    }
+
+    let sql = "SELECT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = $1)";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, opts.member);
+    }
+    let member_exists: bool = client.query_one(sql, &[&opts.member])
+        .await
+        .context("Failed to look up member role")?
+        .get(0);
+    if !member_exists {
+        anyhow::bail!("Role '{}' does not exist; create the user or role first", opts.member);
+    }
+
+    let templates = SqlTemplates::new(
+        opts.database.clone(),
+        mapping.schema_name.clone(),
+        mapping.target_role.clone(),
+        metadata,
+        capabilities,
+    );
+    Ok((client, templates, mapping, mappings))
+}
+
+async fn add_history_table(client: &Client, plan: &mut Plan, templates: &SqlTemplates, verbose: u8) -> Result<()> {
+    let history_table = templates.metadata.qualified_history_table();
+    let sql = "SELECT to_regclass($1) IS NOT NULL";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql, history_table);
+    }
+    let exists: bool = client.query_one(sql, &[&history_table])
+        .await
+        .context("Failed to look up history table")?
+        .get(0);
+    if !exists {
+        plan.add(format!("History table {}", history_table), ActionOutcome::Created, vec![templates.create_history_table()]);
+    }
+    Ok(())
+}
+
+async fn membership(client: &Client, role: &str, member: &str, verbose: u8) -> Result<Option<Membership>> {
+    // inherit_option and set_option only exist from PostgreSQL 16
+    let sql = "
+        SELECT bool_or(m.admin_option),
+               bool_or((to_jsonb(m) ->> 'inherit_option')::boolean),
+               bool_or((to_jsonb(m) ->> 'set_option')::boolean)
+        FROM pg_auth_members m
+        JOIN pg_roles g ON g.oid = m.roleid
+        JOIN pg_roles u ON u.oid = m.member
+        WHERE g.rolname = $1
+          AND u.rolname = $2
+        HAVING count(*) > 0
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), role, member);
+    }
+    let row = client.query_opt(sql, &[&role, &member])
+        .await
+        .context("Failed to query role membership")?;
+    Ok(row.map(|row| Membership { admin: row.get(0), inherit: row.get(1), set: row.get(2) }))
+}
+
+/// Whether `member` belongs to `role` through some role other than a direct grant.
+async fn member_through_other_roles(client: &Client, role: &str, member: &str, verbose: u8) -> Result<bool> {
+    let sql = "
+        WITH RECURSIVE parents AS (
+            SELECT m.roleid
+            FROM pg_auth_members m
+            JOIN pg_roles u ON u.oid = m.member
+            JOIN pg_roles g ON g.oid = m.roleid
+            WHERE u.rolname = $1
+              AND g.rolname <> $2
+            UNION
+            SELECT m.roleid
+            FROM pg_auth_members m
+            JOIN parents ON m.member = parents.roleid
+        )
+        SELECT EXISTS (SELECT 1 FROM parents JOIN pg_roles g ON g.oid = parents.roleid WHERE g.rolname = $2)
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}]", sql.trim(), member, role);
+    }
+    let row = client.query_one(sql, &[&member, &role])
+        .await
+        .context("Failed to query role membership")?;
+    Ok(row.get(0))
+}
+
+/// Active single-schema mappings to `role`; pattern-matched schemas need no
+/// default privileges.
+fn same_role_schemas<'a>(mappings: &'a [Mapping], role: &'a str) -> impl Iterator<Item = &'a Mapping> {
+    mappings.iter().filter(move |m| m.active && !m.is_pattern && m.target_role == role)
+}
+
+fn templates_for(templates: &SqlTemplates, mapping: &Mapping) -> SqlTemplates {
+    SqlTemplates::new(
+        templates.database.clone(),
+        mapping.schema_name.clone(),
+        mapping.target_role.clone(),
+        templates.metadata.clone(),
+        templates.capabilities,
+    )
+}
+
+/// Prints the plan, then runs it in one transaction unless this is a dry run.
+async fn run(client: &mut Client, plan: Plan, command_name: &str, dry_run: bool, verbose: u8) -> Result<()> {
+    plan.print(verbose);
+    if dry_run {
+        println!("Dry run: no changes made.");
+        return Ok(());
+    }
+
+    let transaction = client.transaction().await
+        .context("Failed to start transaction")?;
+    for action in plan.actions() {
+        for sql in &action.statements {
+            if verbose >= 1 {
+                println!("[SQL] {}", sql);
+            }
+            transaction.batch_execute(sql).await
+                .with_context(|| format!("Failed: {}. Nothing was changed.", action.description))?;
+        }
+    }
+    transaction.commit().await
+        .context("Failed to commit")?;
+
+    let mut report = ActionReport::new(command_name);
+    for action in plan.actions() {
+        report.record(action.description.clone(), action.outcome.clone());
+    }
+    report.print_summary();
+    Ok(())
+}
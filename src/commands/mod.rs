@@ -1,2 +1,20 @@
+pub mod apply;
+pub mod bulk_init;
+pub mod create_login;
+pub mod diff;
+pub mod doctor;
+pub mod exec;
+pub mod export;
+pub mod generate_sql;
+pub mod import;
 pub mod init;
+pub mod init_template;
 pub mod list_mappings;
+pub mod list_objects;
+pub mod member;
+pub mod reconcile;
+pub mod remap;
+pub mod sync_default_privileges;
+pub mod uninstall;
+pub mod verify;
+pub mod who_can;
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{discover_metadata, managed_schemas, owned_objects};
+use crate::db::{connect, ConnectionConfig};
+use crate::report::{ActionOutcome, ActionReport};
+use crate::sql_templates::{alter_object_owner, quote_literal};
+
+/// What `reconcile` changes and how.
+#[derive(Debug)]
+pub struct ReconcileOptions {
+    pub database: String,
+    pub schema: Option<String>,
+    /// `pg_identify_object` types such as `table` or `function`; empty means all
+    pub object_types: Vec<String>,
+    pub dry_run: bool,
+    /// Ownership changes committed per transaction
+    pub batch_size: usize,
+    /// `lock_timeout` for each batch, e.g. `5s`
+    pub lock_timeout: String,
+}
+
+/// Transfers objects in managed schemas to their mapped target role, making the
+/// changes the event trigger would have made.
+pub async fn execute(conn_opts: ConnectionConfig, opts: ReconcileOptions, verbose: u8) -> Result<()> {
+    if opts.batch_size == 0 {
+        anyhow::bail!("--batch-size must be at least 1");
+    }
+
+    let mut report = ActionReport::new(if opts.dry_run { "Reconcile (dry run)" } else { "Reconcile" });
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let mut client = connect(&config).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+
+    let managed: Vec<_> = managed_schemas(&client, &metadata, verbose).await?
+        .into_iter()
+        .filter(|m| opts.schema.as_ref().is_none_or(|s| *s == m.schema))
+        .collect();
+    if let Some(schema) = &opts.schema
+        && managed.is_empty()
+    {
+        anyhow::bail!("Schema '{}' is not managed by any mapping in database '{}'", schema, opts.database);
+    }
+
+    for schema in managed.iter().filter(|m| !m.role_exists) {
+        report.record(
+            format!("Schema '{}': target role '{}' does not exist", schema.schema, schema.target_role),
+            ActionOutcome::Skipped,
+        );
+    }
+    let managed: Vec<_> = managed.into_iter().filter(|m| m.role_exists).collect();
+    let schemas: Vec<String> = managed.iter().map(|m| m.schema.clone()).collect();
+
+    // Collect the ownership changes first so nothing is altered while listing
+    let mut changes = Vec::new();
+    for object in owned_objects(&client, &schemas, false, verbose).await? {
+        if !opts.object_types.is_empty() && !opts.object_types.contains(&object.object_type) {
+            continue;
+        }
+        let Some(target) = managed.iter().find(|m| m.schema == object.schema) else {
+            continue;
+        };
+        if object.owner == target.target_role {
+            continue;
+        }
+
+        let description = format!(
+            "{} {} ({} -> {})",
+            object.object_type, object.identity, object.owner, target.target_role
+        );
+
+        // Same rule as the trigger: ALTER TABLE moves sequences owned by its columns
+        if object.owned_by_column {
+            report.record(format!("{} follows its table", description), ActionOutcome::Skipped);
+            continue;
+        }
+
+        match alter_object_owner(&object.object_type, &object.identity, &target.target_role) {
+            Some(sql) => changes.push((description, sql)),
+            None => report.record(format!("{} has no ALTER ... OWNER TO", description), ActionOutcome::Skipped),
+        }
+    }
+
+    if opts.dry_run {
+        for (description, sql) in &changes {
+            println!("[DRY RUN] {}", sql);
+            if verbose >= 1 {
+                println!("          {}", description);
+            }
+        }
+        println!();
+        println!("{} ownership change(s) would be made in {} batch(es).", changes.len(), changes.len().div_ceil(opts.batch_size));
+        report.print_summary();
+        return Ok(());
+    }
+
+    let lock_timeout = format!("SET LOCAL lock_timeout = {}", quote_literal(&opts.lock_timeout));
+    let batch_count = changes.len().div_ceil(opts.batch_size);
+    for (index, batch) in changes.chunks(opts.batch_size).enumerate() {
+        let transaction = client.transaction().await
+            .context("Failed to start transaction")?;
+
+        if verbose >= 1 {
+            println!("[SQL] {}", lock_timeout);
+        }
+        transaction.batch_execute(&lock_timeout).await
+            .context("Failed to set lock_timeout")?;
+
+        for (description, sql) in batch {
+            if verbose >= 1 {
+                println!("[SQL] {}", sql);
+            }
+            if let Err(e) = transaction.execute(sql.as_str(), &[]).await {
+                // Dropping the transaction rolls the batch back
+                drop(transaction);
+                report.print_summary();
+                return Err(e).with_context(|| format!(
+                    "Failed to change owner of {} in batch {} of {}. The batch was rolled back; \
+                     earlier batches are committed. Re-run reconcile to continue.",
+                    description, index + 1, batch_count
+                ));
+            }
+        }
+
+        transaction.commit().await
+            .with_context(|| format!("Failed to commit batch {} of {}", index + 1, batch_count))?;
+        for (description, _) in batch {
+            report.record(description.clone(), ActionOutcome::Updated);
+        }
+    }
+
+    report.print_summary();
+
+    Ok(())
+}
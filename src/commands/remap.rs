@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{default_privilege_grantors, discover_metadata, load_mappings, owned_objects, role_members};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::prompt::confirm;
+use crate::report::{ActionOutcome, ActionReport, Plan};
+use crate::sql_templates::{alter_object_owner, SqlTemplates};
+
+/// Which mapping `remap` moves and to whom.
+#[derive(Debug)]
+pub struct RemapOptions {
+    pub database: String,
+    pub schema: String,
+    /// Role the schema is mapped to now; guards against remapping the wrong schema
+    pub from: String,
+    pub to: String,
+    pub dry_run: bool,
+    pub yes: bool,
+}
+
+/// Moves a schema mapping to a new target role: ownership of the schema and
+/// everything in it, grants, default privileges and the config row, all in one
+/// transaction.
+pub async fn execute(conn_opts: ConnectionConfig, opts: RemapOptions, verbose: u8) -> Result<()> {
+    if opts.from == opts.to {
+        anyhow::bail!("--from and --to are both '{}'", opts.from);
+    }
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let mut client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+
+    let mappings = load_mappings(&client, &metadata, verbose).await?;
+    let mapping = mappings
+        .iter()
+        .find(|m| m.schema_name == opts.schema)
+        .ok_or_else(|| anyhow::anyhow!("Schema '{}' has no mapping in database '{}'", opts.schema, opts.database))?;
+    if mapping.is_pattern {
+        anyhow::bail!("'{}' is a pattern mapping; remap only moves single schemas", opts.schema);
+    }
+    if !mapping.active {
+        anyhow::bail!("The mapping for schema '{}' is inactive (see 'list-mappings')", opts.schema);
+    }
+    if mapping.target_role != opts.from {
+        anyhow::bail!(
+            "Schema '{}' is mapped to role '{}', not '{}'",
+            opts.schema, mapping.target_role, opts.from
+        );
+    }
+
+    let sql = "
+        SELECT pg_get_userbyid(n.nspowner)::text,
+               EXISTS (SELECT 1 FROM pg_roles WHERE rolname = $2),
+               EXISTS (SELECT 1 FROM pg_roles WHERE rolname = $3),
+               to_regclass($4) IS NOT NULL
+        FROM pg_namespace n
+        WHERE n.nspname = $1
+    ";
+    let history_table = metadata.qualified_history_table();
+    if verbose >= 1 {
+        println!(
+            "[SQL] {} -- params: [{}, {}, {}, {}]",
+            sql.trim(), opts.schema, opts.from, opts.to, history_table
+        );
+    }
+    let row = client.query_opt(sql, &[&opts.schema, &opts.from, &opts.to, &history_table])
+        .await
+        .context("Failed to look up schema")?
+        .ok_or_else(|| anyhow::anyhow!("Schema '{}' does not exist in database '{}'", opts.schema, opts.database))?;
+    let schema_owner: String = row.get(0);
+    let from_exists: bool = row.get(1);
+    let to_exists: bool = row.get(2);
+    let history_exists: bool = row.get(3);
+
+    let old = SqlTemplates::new(opts.database.clone(), opts.schema.clone(), opts.from.clone(), metadata.clone(), capabilities);
+    let new = SqlTemplates::new(opts.database.clone(), opts.schema.clone(), opts.to.clone(), metadata.clone(), capabilities);
+
+    let mut plan = Plan::new(format!("Remap schema '{}' from '{}' to '{}'", opts.schema, opts.from, opts.to));
+
+    if to_exists {
+        plan.add(format!("Role '{}'", opts.to), ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add(format!("Role '{}'", opts.to), ActionOutcome::Created, vec![new.create_role()]);
+    }
+    plan.add(format!("CONNECT privilege for '{}'", opts.to), ActionOutcome::Updated, vec![new.grant_connect()]);
+
+    plan.add(
+        format!("History table {}", history_table),
+        if history_exists { ActionOutcome::Skipped } else { ActionOutcome::Created },
+        if history_exists { Vec::new() } else { vec![new.create_history_table()] },
+    );
+    // The mapping moves before any ownership does, so the event trigger firing
+    // on the ALTER statements below already sees the new role
+    plan.add("Mapping and history", ActionOutcome::Updated, old.remap_mapping(&opts.to));
+
+    if schema_owner == opts.to {
+        plan.add("Schema ownership", ActionOutcome::Skipped, Vec::new());
+    } else {
+        plan.add(
+            format!("Schema ownership ({} -> {})", schema_owner, opts.to),
+            ActionOutcome::Updated,
+            vec![new.alter_schema_owner()],
+        );
+    }
+
+    for object in owned_objects(&client, std::slice::from_ref(&opts.schema), false, verbose).await? {
+        if object.object_type == "schema" || object.owner == opts.to {
+            continue;
+        }
+        let description = format!("{} {} ({} -> {})", object.object_type, object.identity, object.owner, opts.to);
+
+        // ALTER TABLE moves sequences owned by its columns
+        if object.owned_by_column {
+            plan.add(format!("{} follows its table", description), ActionOutcome::Skipped, Vec::new());
+            continue;
+        }
+        match alter_object_owner(&object.object_type, &object.identity, &opts.to) {
+            Some(sql) => plan.add(description, ActionOutcome::Updated, vec![sql]),
+            None => plan.add(format!("{} has no ALTER ... OWNER TO", description), ActionOutcome::Skipped, Vec::new()),
+        }
+    }
+
+    plan.add(
+        format!("Grants on schema '{}' for '{}'", opts.schema, opts.to),
+        ActionOutcome::Updated,
+        vec![
+            new.grant_schema_usage(),
+            new.grant_schema_create(),
+            new.grant_all_tables(),
+            new.grant_all_sequences(),
+            new.grant_all_functions(),
+            new.alter_default_privileges_tables(),
+            new.alter_default_privileges_sequences(),
+            new.alter_default_privileges_functions(),
+        ],
+    );
+
+    // Members of the new role create objects as themselves
+    let mut grantors = vec![opts.to.clone()];
+    if to_exists {
+        grantors.extend(role_members(&client, &opts.to, verbose).await?);
+    }
+    for grantor in &grantors {
+        plan.add(
+            format!("Default privileges for role '{}'", grantor),
+            ActionOutcome::Updated,
+            new.alter_default_privileges_for_role(grantor),
+        );
+    }
+    // The old role's grantors are revoked below, so the record starts over
+    let recorded = mappings
+        .iter()
+        .find(|m| m.schema_name == opts.schema)
+        .and_then(|m| m.default_privilege_grantors.as_deref());
+    let mut statements = recorded.map(|r| new.forget_default_privilege_grantors(r)).unwrap_or_default();
+    statements.extend(new.record_default_privilege_grantors(&grantors));
+    plan.add("Default privilege grantors in mapping", ActionOutcome::Updated, statements);
+
+    if from_exists {
+        // Safe to revoke everything now: the old role owns nothing left in the schema
+        let mut statements = old.revoke_all_in_schema();
+        statements.push(old.revoke_schema_grants());
+        plan.add(format!("Grants on schema '{}' for '{}'", opts.schema, opts.from), ActionOutcome::Removed, statements);
+
+        for (grantor, _) in default_privilege_grantors(&client, &opts.schema, &opts.from, verbose).await? {
+            plan.add(
+                format!("Default privileges of '{}' granting to '{}'", grantor, opts.from),
+                ActionOutcome::Removed,
+                old.revoke_default_privileges_for_role(&grantor),
+            );
+        }
+
+        if !mappings.iter().any(|m| m.schema_name != opts.schema && m.target_role == opts.from) {
+            plan.add(format!("CONNECT privilege for '{}'", opts.from), ActionOutcome::Removed, vec![old.revoke_connect()]);
+        }
+    }
+
+    plan.print(verbose);
+
+    if opts.dry_run {
+        println!("Dry run: no changes made.");
+        return Ok(());
+    }
+    if !confirm("Apply this plan?", opts.yes)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let transaction = client.transaction().await
+        .context("Failed to start transaction")?;
+    for action in plan.actions() {
+        for sql in &action.statements {
+            if verbose >= 1 {
+                println!("[SQL] {}", sql);
+            }
+            transaction.batch_execute(sql).await
+                .with_context(|| format!("Failed: {}. Nothing was changed.", action.description))?;
+        }
+    }
+    transaction.commit().await
+        .context("Failed to commit remap")?;
+
+    let mut report = ActionReport::new("Remap");
+    for action in plan.actions() {
+        report.record(action.description.clone(), action.outcome.clone());
+    }
+    report.print_summary();
+
+    Ok(())
+}
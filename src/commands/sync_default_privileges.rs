@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+
+use crate::catalog::{
+    default_privilege_grantors, discover_metadata, has_default_privileges, load_mappings, protected_grantors,
+    revocable_grantors, role_members,
+};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::{ActionOutcome, ActionReport};
+use crate::sql_templates::SqlTemplates;
+
+/// Brings `ALTER DEFAULT PRIVILEGES FOR ROLE` entries in line with the current
+/// membership of each mapped target role: new members get default privileges,
+/// roles that are no longer members have theirs revoked. Only grantors recorded
+/// in the mapping by `init` or an earlier sync are ever revoked, and never the
+/// connected user or the owner of the config table. Each mapping's changes and
+/// the updated record commit in one transaction.
+pub async fn execute(conn_opts: ConnectionConfig, database: String, schema: Option<String>, verbose: u8) -> Result<()> {
+    let mut report = ActionReport::new("Sync Default Privileges");
+
+    let log_sql = |sql: &str| {
+        if verbose >= 1 {
+            println!("[SQL] {}", sql);
+        }
+    };
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some(database.clone());
+    let mut client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            database
+        ))?;
+
+    let mappings: Vec<_> = load_mappings(&client, &metadata, verbose).await?
+        .into_iter()
+        // Schemas matched by a pattern are owned by the target role, which needs no
+        // default privileges on its own objects
+        .filter(|m| m.active && !m.is_pattern)
+        .filter(|m| schema.as_ref().is_none_or(|s| *s == m.schema_name))
+        .collect();
+
+    let protected = protected_grantors(&client, &metadata, verbose).await?;
+
+    if mappings.is_empty() {
+        match &schema {
+            Some(s) => anyhow::bail!("Schema '{}' has no mapping in database '{}'", s, database),
+            None => println!("No schema-to-role mappings found in database '{}'.", database),
+        }
+        return Ok(());
+    }
+
+    for mapping in &mappings {
+        let templates = SqlTemplates::new(
+            database.clone(),
+            mapping.schema_name.clone(),
+            mapping.target_role.clone(),
+            metadata.clone(),
+            capabilities,
+        );
+
+        let mut wanted = vec![mapping.target_role.clone()];
+        wanted.extend(role_members(&client, &mapping.target_role, verbose).await?);
+        let existing = default_privilege_grantors(&client, &mapping.schema_name, &mapping.target_role, verbose).await?;
+
+        // Everything for one mapping, including the grantor record, commits together
+        let mut statements = Vec::new();
+        let mut outcomes = Vec::new();
+
+        for grantor in &wanted {
+            let description = format!(
+                "Default privileges for role '{}' in schema '{}'",
+                grantor, mapping.schema_name
+            );
+            if has_default_privileges(&client, grantor, &mapping.schema_name, &mapping.target_role, verbose).await? {
+                outcomes.push((description, ActionOutcome::Skipped));
+                continue;
+            }
+            statements.extend(templates.alter_default_privileges_for_role(grantor));
+            outcomes.push((description, ActionOutcome::Created));
+        }
+
+        let recorded = mapping.default_privilege_grantors.as_deref();
+        if recorded.is_none_or(|recorded| wanted.iter().any(|g| !recorded.contains(g))) {
+            statements.extend(templates.record_default_privilege_grantors(&wanted));
+        }
+        if let Some(recorded) = recorded {
+            // Defaults someone else set up (such as the role that ran `init`) are not
+            // tied to membership, so only grantors the tool added are revoked
+            for grantor in revocable_grantors(&existing, Some(recorded), &protected, &wanted) {
+                statements.extend(templates.revoke_default_privileges_for_role(&grantor));
+                outcomes.push((
+                    format!("Default privileges for former member '{}' in schema '{}'", grantor, mapping.schema_name),
+                    ActionOutcome::Removed,
+                ));
+            }
+            let former: Vec<String> = recorded.iter().filter(|g| !wanted.contains(g)).cloned().collect();
+            if !former.is_empty() {
+                statements.extend(templates.forget_default_privilege_grantors(&former));
+            }
+        }
+
+        let transaction = client.transaction().await
+            .context("Failed to start transaction")?;
+        for sql in &statements {
+            log_sql(sql);
+            transaction.batch_execute(sql).await
+                .with_context(|| format!(
+                    "Failed to sync default privileges in schema '{}'. Nothing was changed for this schema.",
+                    mapping.schema_name
+                ))?;
+        }
+        transaction.commit().await
+            .context("Failed to commit")?;
+
+        for (description, outcome) in outcomes {
+            report.record(description, outcome);
+        }
+        if recorded.is_none() {
+            println!(
+                "Note: The mapping of schema '{}' had no record of the roles given default privileges, \
+                 so none were revoked. Current members are recorded from now on.",
+                mapping.schema_name
+            );
+        }
+    }
+
+    report.print_summary();
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::catalog::{default_privilege_grantors, discover_metadata, load_mappings, protected_grantors, revocable_grantors};
+use crate::commands::init::apply_in_transaction;
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::prompt::confirm;
+use crate::report::{ActionOutcome, ActionReport, Plan};
+use crate::sql_templates::SqlTemplates;
+
+/// What `uninstall` removes beyond the mappings themselves.
+#[derive(Debug)]
+pub struct UninstallOptions {
+    pub database: String,
+    /// Only remove this mapping; `None` removes every mapping in the database
+    pub schema: Option<String>,
+    pub revoke_grants: bool,
+    pub drop_schema: bool,
+    pub drop_role: bool,
+    /// Answer yes to every confirmation prompt
+    pub yes: bool,
+}
+
+/// Removes the selected mappings, and the installation once none remain. Every
+/// database change commits in one transaction; only `--drop-role` runs after it,
+/// since a role can still hold objects in other databases and failing to drop it
+/// should not undo the rest.
+pub async fn execute(conn_opts: ConnectionConfig, opts: UninstallOptions, verbose: u8) -> Result<()> {
+    let mut report = ActionReport::new("Uninstall");
+
+    let log_sql = |sql: &str| {
+        if verbose >= 1 {
+            println!("[SQL] {}", sql);
+        }
+    };
+
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let mut client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Nothing to uninstall.",
+            opts.database
+        ))?;
+
+    let mappings = load_mappings(&client, &metadata, verbose).await?;
+    let protected = protected_grantors(&client, &metadata, verbose).await?;
+    let (selected, remaining): (Vec<_>, Vec<_>) = mappings
+        .into_iter()
+        .partition(|m| opts.schema.as_ref().is_none_or(|s| *s == m.schema_name));
+
+    match &opts.schema {
+        Some(schema) if selected.is_empty() => {
+            anyhow::bail!("Schema '{}' has no mapping in database '{}'", schema, opts.database);
+        }
+        Some(_) => {}
+        None => {
+            let question = format!(
+                "Remove all {} mapping(s) and the event triggers, functions and config table from database '{}'?",
+                selected.len(),
+                opts.database
+            );
+            if !confirm(&question, opts.yes)? {
+                println!("Aborted.");
+                return Ok(());
+            }
+        }
+    }
+
+    if opts.drop_schema
+        && let Some(pattern) = selected.iter().find(|m| m.is_pattern)
+    {
+        anyhow::bail!(
+            "--drop-schema cannot be used with the pattern mapping '{}'; drop matching schemas individually",
+            pattern.schema_name
+        );
+    }
+
+    // The pattern stays installed while other schemas are still managed. Removing
+    // the last one by name also drops the history, so that needs its own yes
+    let tear_down = remaining.is_empty()
+        && (opts.schema.is_none()
+            || confirm(
+                &format!(
+                    "That is the last mapping in database '{}'. Also drop the event triggers, functions, config table and its history?",
+                    opts.database
+                ),
+                opts.yes,
+            )?);
+
+    let mut plan = Plan::new("Uninstall Plan");
+    for mapping in &selected {
+        let templates = SqlTemplates::new(
+            opts.database.clone(),
+            mapping.schema_name.clone(),
+            mapping.target_role.clone(),
+            metadata.clone(),
+            capabilities,
+        );
+
+        // The history table goes with the installation; otherwise it keeps the removal
+        let mut statements = vec![templates.delete_mapping()];
+        if !tear_down {
+            statements.push(templates.create_history_table());
+            statements.push(templates.record_uninstall());
+        }
+        plan.add(
+            format!("Mapping '{}' -> '{}'", mapping.schema_name, mapping.target_role),
+            ActionOutcome::Removed,
+            statements,
+        );
+
+        let schema_info = if mapping.is_pattern {
+            None
+        } else {
+            schema_info(&client, &mapping.schema_name, verbose).await?
+        };
+
+        // Dropping the role needs its privileges in this database gone as well
+        if (opts.revoke_grants || opts.drop_role)
+            && mapping.role_exists
+            && let Some((owner, _)) = &schema_info
+        {
+            let sql = templates.revoke_object_grants_query();
+            log_sql(&sql);
+            let mut revokes: Vec<String> = client.query(&sql, &[]).await
+                .context("Failed to list object grants")?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+
+            // An owner's privileges are its own, not grants made by init
+            if *owner != mapping.target_role {
+                revokes.push(templates.revoke_schema_grants());
+            }
+            plan.add(
+                format!("Grants on schema '{}' for '{}'", mapping.schema_name, mapping.target_role),
+                ActionOutcome::Removed,
+                revokes,
+            );
+
+            // Only defaults the tool recorded adding, as sync-default-privileges revokes them
+            let existing = default_privilege_grantors(&client, &mapping.schema_name, &mapping.target_role, verbose).await?;
+            let recorded = mapping.default_privilege_grantors.as_deref();
+            if recorded.is_none() && !existing.is_empty() {
+                println!(
+                    "Note: The mapping of schema '{}' has no record of the roles given default privileges, so none are revoked.",
+                    mapping.schema_name
+                );
+            }
+            for grantor in revocable_grantors(&existing, recorded, &protected, &[]) {
+                plan.add(
+                    format!("Default privileges for role '{}' in schema '{}'", grantor, mapping.schema_name),
+                    ActionOutcome::Removed,
+                    templates.revoke_default_privileges_for_role(&grantor),
+                );
+            }
+        }
+
+        if opts.drop_schema {
+            match schema_info {
+                Some((_, count)) => {
+                    let question = format!(
+                        "Drop schema '{}' and the {} table(s), view(s) and sequence(s) in it?",
+                        mapping.schema_name, count
+                    );
+                    if confirm(&question, opts.yes)? {
+                        plan.add(format!("Schema '{}'", mapping.schema_name), ActionOutcome::Removed, vec![templates.drop_schema()]);
+                    } else {
+                        plan.add(format!("Schema '{}'", mapping.schema_name), ActionOutcome::Skipped, Vec::new());
+                    }
+                }
+                None => plan.add(format!("Schema '{}' (does not exist)", mapping.schema_name), ActionOutcome::Skipped, Vec::new()),
+            }
+        }
+    }
+
+    if remaining.is_empty() && !tear_down {
+        plan.add("Event triggers, functions and config table (kept)", ActionOutcome::Skipped, Vec::new());
+    } else if tear_down {
+        let templates = SqlTemplates::new(opts.database.clone(), String::new(), String::new(), metadata.clone(), capabilities);
+
+        // Event triggers first: the ownership trigger reads the config table
+        plan.add(
+            format!("Event triggers '{}', '{}'", metadata.event_trigger, metadata.cleanup_event_trigger),
+            ActionOutcome::Removed,
+            templates.drop_event_triggers(&metadata),
+        );
+        plan.add(
+            format!("Functions {}(), {}()", metadata.qualified_function(), metadata.qualified_cleanup_function()),
+            ActionOutcome::Removed,
+            templates.drop_trigger_functions(&metadata),
+        );
+        plan.add(
+            format!("Config table {}", metadata.qualified_config_table()),
+            ActionOutcome::Removed,
+            vec![templates.drop_config_table()],
+        );
+    }
+
+    // Roles still mapped to another schema keep their database-level grants
+    let mut roles: Vec<&String> = selected
+        .iter()
+        .filter(|m| m.role_exists)
+        .map(|m| &m.target_role)
+        .filter(|role| !remaining.iter().any(|m| m.target_role == **role))
+        .collect();
+    roles.sort();
+    roles.dedup();
+
+    if opts.revoke_grants || opts.drop_role {
+        for role in &roles {
+            let templates = SqlTemplates::new(opts.database.clone(), String::new(), (*role).clone(), metadata.clone(), capabilities);
+            plan.add(format!("CONNECT privilege for '{}'", role), ActionOutcome::Removed, vec![templates.revoke_connect()]);
+        }
+    }
+
+    // Asked up front, so every question is answered before anything changes
+    let mut drop_roles = Vec::new();
+    if opts.drop_role {
+        for role in roles {
+            if confirm(&format!("Drop role '{}'?", role), opts.yes)? {
+                drop_roles.push(role);
+            } else {
+                plan.add(format!("Role '{}'", role), ActionOutcome::Skipped, Vec::new());
+            }
+        }
+    }
+
+    apply_in_transaction(&mut client, plan.actions(), &mut report, verbose).await?;
+    if !tear_down {
+        println!("{} mapping(s) remain; event triggers, functions and config table are kept.", remaining.len());
+    }
+
+    // Roles are cluster-wide and may still own objects elsewhere, so they are
+    // dropped one by one after the database changes have committed
+    for role in drop_roles {
+        let templates = SqlTemplates::new(opts.database.clone(), String::new(), role.clone(), metadata.clone(), capabilities);
+        let sql = templates.drop_role();
+        log_sql(&sql);
+        client.execute(&sql, &[]).await
+            .with_context(|| format!(
+                "Failed to drop role '{}'; the rest of the uninstall has been committed. It may still own objects \
+                 or hold privileges in this or other databases (see REASSIGN OWNED / DROP OWNED).",
+                role
+            ))?;
+        report.record(format!("Role '{}'", role), ActionOutcome::Removed);
+    }
+
+    report.print_summary();
+
+    Ok(())
+}
+
+/// Owner of `schema` and the number of relations in it, or `None` if the schema
+/// does not exist.
+async fn schema_info(client: &Client, schema: &str, verbose: u8) -> Result<Option<(String, i64)>> {
+    let sql = "
+        SELECT pg_get_userbyid(n.nspowner)::text,
+               (SELECT count(*) FROM pg_class c WHERE c.relnamespace = n.oid AND c.relkind IN ('r', 'p', 'v', 'm', 'S', 'f'))
+        FROM pg_namespace n
+        WHERE n.nspname = $1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), schema);
+    }
+    let row = client.query_opt(sql, &[&schema])
+        .await
+        .context("Failed to look up schema")?;
+    Ok(row.map(|row| (row.get(0), row.get(1))))
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+
+use crate::catalog::{discover_metadata, managed_schemas, owned_objects, user_databases};
+use crate::db::{connect, ConnectionConfig};
+use crate::report::truncate_with_ellipsis;
+
+/// Narrows which databases, schemas and object types `verify` looks at.
+#[derive(Debug, Default)]
+pub struct VerifyFilters {
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    /// `pg_identify_object` types such as `table` or `function`; empty means all
+    pub object_types: Vec<String>,
+}
+
+struct Drift {
+    database: String,
+    schema: String,
+    object_type: String,
+    identity: String,
+    owner: String,
+    expected: String,
+}
+
+/// Lists objects in managed schemas whose owner is not the mapped target role.
+/// Fails (nonzero exit) when any drift is found or a database could not be
+/// checked.
+pub async fn execute(conn_opts: ConnectionConfig, filters: VerifyFilters, verbose: u8) -> Result<()> {
+    let databases = match &filters.database {
+        Some(database) => vec![database.clone()],
+        None => {
+            let mut config = conn_opts.clone();
+            config.dbname = Some("postgres".to_string());
+            let client = connect(&config).await?;
+            user_databases(&client, verbose).await?
+        }
+    };
+
+    let mut drifted = Vec::new();
+    let mut checked_objects = 0;
+    let mut checked_schemas = 0;
+    // Databases that could not be connected to, with the error
+    let mut unreachable = Vec::new();
+
+    for database in &databases {
+        let mut config = conn_opts.clone();
+        config.dbname = Some(database.clone());
+        let client = match connect(&config).await {
+            Ok(client) => client,
+            Err(e) => {
+                // Unreadable databases fail the run instead of being skipped
+                unreachable.push((database.clone(), format!("{:#}", e).replace('\n', " ")));
+                continue;
+            }
+        };
+
+        let Some(metadata) = discover_metadata(&client, verbose).await? else {
+            if filters.database.is_some() {
+                anyhow::bail!("Database '{}' has no schema_ownership_config. Run 'init' first.", database);
+            }
+            continue;
+        };
+
+        let managed: Vec<_> = managed_schemas(&client, &metadata, verbose).await?
+            .into_iter()
+            .filter(|m| filters.schema.as_ref().is_none_or(|s| *s == m.schema))
+            .collect();
+
+        for schema in managed.iter().filter(|m| !m.role_exists) {
+            println!(
+                "Warning: Skipping {}.{}: target role '{}' does not exist (see 'doctor')",
+                database, schema.schema, schema.target_role
+            );
+        }
+        let managed: Vec<_> = managed.into_iter().filter(|m| m.role_exists).collect();
+        if managed.is_empty() {
+            continue;
+        }
+        checked_schemas += managed.len();
+
+        let schemas: Vec<String> = managed.iter().map(|m| m.schema.clone()).collect();
+        let objects = owned_objects(&client, &schemas, false, verbose).await?;
+
+        for object in objects {
+            if !filters.object_types.is_empty() && !filters.object_types.contains(&object.object_type) {
+                continue;
+            }
+            checked_objects += 1;
+
+            let Some(expected) = managed.iter().find(|m| m.schema == object.schema) else {
+                continue;
+            };
+            if object.owner != expected.target_role {
+                drifted.push(Drift {
+                    database: database.clone(),
+                    schema: object.schema,
+                    object_type: object.object_type,
+                    identity: object.identity,
+                    owner: object.owner,
+                    expected: expected.target_role.clone(),
+                });
+            }
+        }
+    }
+
+    if let Some(schema) = &filters.schema
+        && checked_schemas == 0
+        && unreachable.is_empty()
+    {
+        anyhow::bail!("Schema '{}' is not managed by any mapping", schema);
+    }
+
+    for (database, error) in &unreachable {
+        println!("Could not check database '{}': {}", database, error);
+    }
+    if !unreachable.is_empty() {
+        println!();
+    }
+
+    if drifted.is_empty() {
+        println!(
+            "No ownership drift: {} object(s) in {} managed schema(s) owned by their target role.",
+            checked_objects, checked_schemas
+        );
+        if !unreachable.is_empty() {
+            anyhow::bail!("{} database(s) could not be checked", unreachable.len());
+        }
+        return Ok(());
+    }
+
+    println!("{:<20} {:<20} {:<26} {:<50} {:<20} {:<20}", "Database", "Schema", "Type", "Object", "Owner", "Expected Owner");
+    println!("{}", "-".repeat(161));
+    for drift in &drifted {
+        println!(
+            "{:<20} {:<20} {:<26} {:<50} {:<20} {:<20}",
+            truncate_with_ellipsis(&drift.database, 20),
+            truncate_with_ellipsis(&drift.schema, 20),
+            drift.object_type,
+            truncate_with_ellipsis(&drift.identity, 50),
+            truncate_with_ellipsis(&drift.owner, 20),
+            truncate_with_ellipsis(&drift.expected, 20)
+        );
+    }
+
+    println!();
+    println!(
+        "{} of {} object(s) in {} managed schema(s) not owned by their target role.",
+        drifted.len(), checked_objects, checked_schemas
+    );
+
+    if !unreachable.is_empty() {
+        anyhow::bail!(
+            "Ownership drift found in {} object(s); {} database(s) could not be checked",
+            drifted.len(), unreachable.len()
+        );
+    }
+    anyhow::bail!("Ownership drift found in {} object(s)", drifted.len());
+}
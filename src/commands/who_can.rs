@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{Context, Result};
+use tokio_postgres::Client;
+
+use crate::catalog::{discover_metadata, managed_schemas};
+use crate::db::{connect, server_capabilities, ConnectionConfig};
+use crate::report::truncate_with_ellipsis;
+
+/// The schema whose effective privileges `who-can` reports.
+#[derive(Debug)]
+pub struct WhoCanOptions {
+    pub database: String,
+    pub schema: String,
+}
+
+/// A `pg_auth_members` grant, with what it passes on to the member.
+struct Edge {
+    role: String,
+    /// The member gets the role's privileges without SET ROLE
+    inherit: bool,
+    /// The member may SET ROLE to the role
+    set: bool,
+}
+
+/// What a login role can do in the schema, as PostgreSQL decides it.
+struct LoginPrivileges {
+    login: String,
+    superuser: bool,
+    create: bool,
+    usage: bool,
+    /// Has the target role's privileges without SET ROLE
+    inherits_target: bool,
+    /// May SET ROLE to the target role
+    can_set_target: bool,
+    readable: i64,
+    writable: i64,
+}
+
+/// Lists, for every login role, whether it can create objects in a managed
+/// schema, whether it holds the privileges of the role the trigger hands them
+/// to, or only has access, and through which chain of memberships.
+pub async fn execute(conn_opts: ConnectionConfig, opts: WhoCanOptions, verbose: u8) -> Result<()> {
+    let mut config = conn_opts.clone();
+    config.dbname = Some(opts.database.clone());
+    let client = connect(&config).await?;
+    let capabilities = server_capabilities(&client).await?;
+
+    let metadata = discover_metadata(&client, verbose).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "Database '{}' has no schema_ownership_config. Run 'init' first.",
+            opts.database
+        ))?;
+    let managed = managed_schemas(&client, &metadata, verbose).await?
+        .into_iter()
+        .find(|m| m.schema == opts.schema)
+        .ok_or_else(|| anyhow::anyhow!(
+            "Schema '{}' is not managed in database '{}' (see 'list-mappings')",
+            opts.schema, opts.database
+        ))?;
+    if !managed.role_exists {
+        anyhow::bail!("Target role '{}' of schema '{}' does not exist", managed.target_role, opts.schema);
+    }
+    let target = managed.target_role.as_str();
+
+    let edges = membership_edges(&client, verbose).await?;
+    // Before PostgreSQL 16 every member may SET ROLE; from 16 the grant's SET option decides
+    let set_privilege = if capabilities.has_membership_options() { "SET" } else { "MEMBER" };
+    let (table_count, logins) = login_privileges(&client, &opts.schema, target, set_privilege, verbose).await?;
+    let (create_grantees, table_grantees) = grantees(&client, &opts.schema, verbose).await?;
+
+    println!("Schema {}.{} is mapped to '{}' ({} table(s) and view(s)).", opts.database, opts.schema, target, table_count);
+    println!();
+
+    let target_set = HashSet::from([target.to_string()]);
+    let mut rows = Vec::new();
+    for login in &logins {
+        let row = if login.superuser {
+            ("yes".to_string(), "yes".to_string(), "all (superuser)".to_string(), "superuser".to_string())
+        } else if login.inherits_target {
+            let path = find_path(&edges, &login.login, &target_set, |e| e.inherit);
+            ("yes".to_string(), "yes".to_string(), "all (owner)".to_string(), format_path(path))
+        } else if login.can_set_target {
+            let path = find_path(&edges, &login.login, &target_set, |e| e.set);
+            let create = if login.create { "yes" } else { "after SET ROLE" };
+            (create.to_string(), "after SET ROLE".to_string(), access(login, table_count), format_path(path))
+        } else if login.create || login.usage || login.readable > 0 {
+            let grantees = if login.create { &create_grantees } else { &table_grantees };
+            let path = if grantees.contains("PUBLIC") && find_path(&edges, &login.login, grantees, |e| e.inherit).is_none() {
+                "PUBLIC".to_string()
+            } else {
+                format_path(find_path(&edges, &login.login, grantees, |e| e.inherit))
+            };
+            let create = if login.create { format!("yes, handed to {}", target) } else { "no".to_string() };
+            (create, "no".to_string(), access(login, table_count), path)
+        } else {
+            continue;
+        };
+        rows.push((login.login.as_str(), row));
+    }
+
+    if rows.is_empty() {
+        println!("No login role has privileges in schema '{}'.", opts.schema);
+        return Ok(());
+    }
+
+    println!("{:<24} {:<28} {:<16} {:<26} Path", "Login", "Can Create", "Owns Objects", "Access");
+    println!("{}", "-".repeat(130));
+    for (login, (create, owns, access, path)) in &rows {
+        println!(
+            "{:<24} {:<28} {:<16} {:<26} {}",
+            truncate_with_ellipsis(login, 24),
+            create,
+            owns,
+            access,
+            path
+        );
+    }
+    println!();
+    println!("Objects created in the schema are owned by '{}'; 'Owns Objects' means the login holds that role's privileges.", target);
+    Ok(())
+}
+
+fn access(login: &LoginPrivileges, table_count: i64) -> String {
+    if !login.usage {
+        "none (no USAGE)".to_string()
+    } else if login.writable > 0 {
+        format!("write {}/{}, read {}/{}", login.writable, table_count, login.readable, table_count)
+    } else if login.readable > 0 {
+        format!("read {}/{}", login.readable, table_count)
+    } else {
+        "USAGE only".to_string()
+    }
+}
+
+fn format_path(path: Option<Vec<String>>) -> String {
+    match path {
+        Some(path) if path.len() == 1 => "direct grant".to_string(),
+        Some(path) => path.join(" -> "),
+        None => "-".to_string(),
+    }
+}
+
+/// Shortest chain of memberships from `login` to one of `roles`, following only
+/// grants that `follow` accepts. The chain starts with `login`.
+fn find_path(
+    edges: &HashMap<String, Vec<Edge>>,
+    login: &str,
+    roles: &HashSet<String>,
+    follow: impl Fn(&Edge) -> bool,
+) -> Option<Vec<String>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([login]);
+    let mut seen = HashSet::from([login]);
+    while let Some(role) = queue.pop_front() {
+        if roles.contains(role) {
+            let mut path = vec![role.to_string()];
+            let mut current = role;
+            while let Some(&parent) = previous.get(current) {
+                path.push(parent.to_string());
+                current = parent;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for edge in edges.get(role).into_iter().flatten().filter(|e| follow(e)) {
+            if seen.insert(edge.role.as_str()) {
+                previous.insert(edge.role.as_str(), role);
+                queue.push_back(edge.role.as_str());
+            }
+        }
+    }
+    None
+}
+
+/// All role grants, keyed by member.
+async fn membership_edges(client: &Client, verbose: u8) -> Result<HashMap<String, Vec<Edge>>> {
+    // inherit_option and set_option only exist from PostgreSQL 16; before that the
+    // member's INHERIT attribute decides, and every member may SET ROLE
+    let sql = "
+        SELECT u.rolname::text, g.rolname::text,
+               bool_or(COALESCE((to_jsonb(m) ->> 'inherit_option')::boolean, u.rolinherit)),
+               bool_or(COALESCE((to_jsonb(m) ->> 'set_option')::boolean, true))
+        FROM pg_auth_members m
+        JOIN pg_roles g ON g.oid = m.roleid
+        JOIN pg_roles u ON u.oid = m.member
+        GROUP BY 1, 2
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {}", sql.trim());
+    }
+    let rows = client.query(sql, &[])
+        .await
+        .context("Failed to query role memberships")?;
+
+    let mut edges: HashMap<String, Vec<Edge>> = HashMap::new();
+    for row in rows {
+        edges.entry(row.get(0)).or_default().push(Edge {
+            role: row.get(1),
+            inherit: row.get(2),
+            set: row.get(3),
+        });
+    }
+    Ok(edges)
+}
+
+/// Number of tables and views in the schema, and what each login role may do there.
+async fn login_privileges(
+    client: &Client,
+    schema: &str,
+    target: &str,
+    set_privilege: &str,
+    verbose: u8,
+) -> Result<(i64, Vec<LoginPrivileges>)> {
+    let sql = "
+        WITH tables AS (
+            SELECT c.oid
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = $1
+              AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+        )
+        SELECT r.rolname::text, r.rolsuper,
+               has_schema_privilege(r.oid, n.oid, 'CREATE'),
+               has_schema_privilege(r.oid, n.oid, 'USAGE'),
+               pg_has_role(r.oid, t.oid, 'USAGE'),
+               pg_has_role(r.oid, t.oid, $3),
+               (SELECT count(*) FROM tables WHERE has_table_privilege(r.oid, tables.oid, 'SELECT')),
+               (SELECT count(*) FROM tables WHERE has_table_privilege(r.oid, tables.oid, 'INSERT, UPDATE, DELETE')),
+               (SELECT count(*) FROM tables)
+        FROM pg_roles r
+        CROSS JOIN pg_namespace n
+        CROSS JOIN pg_roles t
+        WHERE r.rolcanlogin
+          AND n.nspname = $1
+          AND t.rolname = $2
+        ORDER BY 1
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}, {}, {}]", sql.trim(), schema, target, set_privilege);
+    }
+    let rows = client.query(sql, &[&schema, &target, &set_privilege])
+        .await
+        .context("Failed to query effective privileges")?;
+    if rows.is_empty() {
+        // Either no login roles, or the schema does not exist
+        let sql = "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)";
+        let exists: bool = client.query_one(sql, &[&schema])
+            .await
+            .context("Failed to look up schema")?
+            .get(0);
+        if !exists {
+            anyhow::bail!("Schema '{}' does not exist", schema);
+        }
+        return Ok((0, Vec::new()));
+    }
+
+    let table_count = rows[0].get(8);
+    let logins = rows
+        .iter()
+        .map(|row| LoginPrivileges {
+            login: row.get(0),
+            superuser: row.get(1),
+            create: row.get(2),
+            usage: row.get(3),
+            inherits_target: row.get(4),
+            can_set_target: row.get(5),
+            readable: row.get(6),
+            writable: row.get(7),
+        })
+        .collect();
+    Ok((table_count, logins))
+}
+
+/// Roles holding CREATE on the schema, and roles holding any privilege on its
+/// tables and views (owners included); `PUBLIC` stands for grants to everyone.
+async fn grantees(client: &Client, schema: &str, verbose: u8) -> Result<(HashSet<String>, HashSet<String>)> {
+    let sql = "
+        SELECT 'create', pg_get_userbyid(n.nspowner)::text
+        FROM pg_namespace n
+        WHERE n.nspname = $1
+        UNION
+        SELECT 'create', CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE pg_get_userbyid(a.grantee)::text END
+        FROM pg_namespace n
+        CROSS JOIN LATERAL aclexplode(n.nspacl) a
+        WHERE n.nspname = $1
+          AND a.privilege_type = 'CREATE'
+        UNION
+        SELECT 'table', pg_get_userbyid(c.relowner)::text
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = $1
+          AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+        UNION
+        SELECT 'table', CASE WHEN a.grantee = 0 THEN 'PUBLIC' ELSE pg_get_userbyid(a.grantee)::text END
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        CROSS JOIN LATERAL aclexplode(c.relacl) a
+        WHERE n.nspname = $1
+          AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+    ";
+    if verbose >= 1 {
+        println!("[SQL] {} -- params: [{}]", sql.trim(), schema);
+    }
+    let rows = client.query(sql, &[&schema])
+        .await
+        .context("Failed to query schema privileges")?;
+
+    let mut create = HashSet::new();
+    let mut table = HashSet::new();
+    for row in rows {
+        let kind: &str = row.get(0);
+        let role: String = row.get(1);
+        if kind == "create" {
+            create.insert(role);
+        } else {
+            table.insert(role);
+        }
+    }
+    Ok((create, table))
+}
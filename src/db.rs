@@ -6,9 +6,10 @@ use rustls::{DigitallySignedStruct, SignatureScheme};
 use std::sync::Arc;
 use tokio_postgres::{Client, NoTls};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum SslMode {
     Disable,
+    #[default]
     Prefer,
     Require,
 }
@@ -25,11 +26,14 @@ impl SslMode {
             )),
         }
     }
-}
 
-impl Default for SslMode {
-    fn default() -> Self {
-        SslMode::Prefer
+    /// The libpq `sslmode` value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+        }
     }
 }
 
@@ -51,6 +55,107 @@ impl ConnectionConfig {
             self.host, self.port, self.user, self.password, dbname
         )
     }
+
+    /// Copy with the fields set in a libpq connection string (`host=db2 dbname=app`
+    /// or `postgresql://user@db2/app`) replaced. TLS still follows `--sslmode`.
+    pub fn with_conninfo(&self, conninfo: &str) -> Result<Self> {
+        let parsed: tokio_postgres::Config = conninfo.parse()
+            .with_context(|| format!("Invalid connection string '{}'", conninfo))?;
+
+        let mut config = self.clone();
+        if let Some(host) = parsed.get_hosts().first() {
+            config.host = match host {
+                tokio_postgres::config::Host::Tcp(host) => host.clone(),
+                #[cfg(unix)]
+                tokio_postgres::config::Host::Unix(path) => path.to_string_lossy().into_owned(),
+            };
+        }
+        if let Some(port) = parsed.get_ports().first() {
+            config.port = *port;
+        }
+        if let Some(user) = parsed.get_user() {
+            config.user = user.to_string();
+        }
+        if let Some(password) = parsed.get_password() {
+            config.password = String::from_utf8_lossy(password).into_owned();
+        }
+        if let Some(dbname) = parsed.get_dbname() {
+            config.dbname = Some(dbname.to_string());
+        }
+        Ok(config)
+    }
+
+    /// `host:port`, plus `/dbname` when one is set.
+    pub fn display_target(&self) -> String {
+        match &self.dbname {
+            Some(dbname) => format!("{}:{}/{}", self.host, self.port, dbname),
+            None => format!("{}:{}", self.host, self.port),
+        }
+    }
+}
+
+/// Oldest server release `init` can install the pattern on.
+pub const MIN_SERVER_VERSION_NUM: i32 = 100000;
+
+/// Features of the connected server that change the SQL we generate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerCapabilities {
+    /// `server_version_num`, e.g. 150004 for 15.4
+    pub version_num: i32,
+}
+
+impl ServerCapabilities {
+    pub fn from_version_num(version_num: i32) -> Self {
+        Self { version_num }
+    }
+
+    pub fn major_version(&self) -> i32 {
+        self.version_num / 10000
+    }
+
+    /// `CREATE EVENT TRIGGER ... EXECUTE FUNCTION` replaced `EXECUTE PROCEDURE` in 11
+    pub fn event_trigger_execute_keyword(&self) -> &'static str {
+        if self.version_num >= 110000 { "FUNCTION" } else { "PROCEDURE" }
+    }
+
+    /// `CREATE PROCEDURE` exists from 11
+    pub fn has_procedures(&self) -> bool {
+        self.version_num >= 110000
+    }
+
+    /// `GRANT role TO member WITH INHERIT/SET` options exist from 16
+    pub fn has_membership_options(&self) -> bool {
+        self.version_num >= 160000
+    }
+
+    /// The `MAINTAIN` table privilege (VACUUM, ANALYZE, REFRESH MATERIALIZED VIEW, ...) exists from 17
+    pub fn has_maintain_privilege(&self) -> bool {
+        self.version_num >= 170000
+    }
+
+    /// From 15, PUBLIC no longer has CREATE on the `public` schema of new databases
+    pub fn public_schema_create_restricted(&self) -> bool {
+        self.version_num >= 150000
+    }
+}
+
+/// Reads `server_version_num` from the connected server.
+pub async fn server_capabilities(client: &Client) -> Result<ServerCapabilities> {
+    let row = client
+        .query_one("SELECT current_setting('server_version_num')::int", &[])
+        .await
+        .context("Failed to read server_version_num")?;
+    let caps = ServerCapabilities::from_version_num(row.get(0));
+
+    if caps.version_num < MIN_SERVER_VERSION_NUM {
+        anyhow::bail!(
+            "PostgreSQL {} is not supported. Version {} or newer is required.",
+            caps.major_version(),
+            MIN_SERVER_VERSION_NUM / 10000
+        );
+    }
+
+    Ok(caps)
 }
 
 /// Custom certificate verifier that accepts all certificates without validation.
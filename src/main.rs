@@ -1,17 +1,63 @@
+mod catalog;
 mod cli;
 mod commands;
 mod db;
+mod manifest;
+mod prompt;
 mod report;
 mod sql_templates;
 
 use anyhow::Result;
 use clap::Parser;
-use cli::{Cli, Command};
+use cli::{Cli, Command, MetadataArgs};
 use db::{ConnectionConfig, SslMode};
+use sql_templates::{MappingOptions, MembershipOptions, MissingRolePolicy, PresetRoles};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Cli::parse();
+    let verbose = args.connection.verbose;
+
+    // Commands that never connect run before credentials are checked
+    let command = match args.command {
+        Command::GenerateSql {
+            database,
+            schema,
+            schema_pattern,
+            role,
+            metadata,
+            report_extension_owner,
+            on_missing_role,
+            server_version,
+            output,
+        } => {
+            let (schema, schema_is_pattern) = match (schema, schema_pattern) {
+                (Some(schema), _) => (schema, false),
+                (None, Some(pattern)) => (pattern, true),
+                (None, None) => unreachable!(),
+            };
+
+            let opts = commands::generate_sql::GenerateSqlOptions {
+                database,
+                schema,
+                schema_is_pattern,
+                role,
+                metadata: metadata_options(metadata),
+                mapping: MappingOptions {
+                    report_extension_owner,
+                    on_missing_role: on_missing_role.as_deref().map(MissingRolePolicy::from_str).transpose()?,
+                },
+                server_version,
+                output,
+            };
+            return commands::generate_sql::execute(opts);
+        }
+        Command::Version => {
+            println!("{}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        command => command,
+    };
 
     // Parse SSL mode
     let sslmode = SslMode::from_str(&args.connection.sslmode)?;
@@ -19,31 +65,289 @@ async fn main() -> Result<()> {
     let conn_config = ConnectionConfig {
         host: args.connection.host,
         port: args.connection.port,
-        user: args.connection.user,
-        password: args.connection.password,
+        user: args.connection.user
+            .ok_or_else(|| anyhow::anyhow!("User must be specified via --user flag or PGUSER environment variable"))?,
+        password: args.connection.password
+            .ok_or_else(|| anyhow::anyhow!("Password must be specified via --password flag or PGPASSWORD environment variable"))?,
         dbname: args.connection.dbname,
         sslmode,
     };
 
-    let verbose = args.connection.verbose;
-
-    match args.command {
-        Command::Init { database, schema, role } => {
+    match command {
+        Command::Init { database_pattern, database_list, schema, schema_pattern, jobs, dry_run, .. }
+            if database_pattern.is_some() || database_list.is_some() =>
+        {
+            let selection = match (database_pattern, database_list) {
+                (Some(pattern), _) => commands::bulk_init::DatabaseSelection::Pattern(pattern),
+                (None, Some(path)) => commands::bulk_init::DatabaseSelection::List(path),
+                (None, None) => unreachable!(),
+            };
+            let opts = commands::bulk_init::BulkInitOptions {
+                selection,
+                // clap guarantees exactly one of --schema and --schema-pattern
+                schema: schema.or(schema_pattern).expect("schema or schema pattern is required"),
+                jobs,
+                dry_run,
+            };
+            commands::bulk_init::execute(conn_config, opts, verbose).await?;
+        }
+        Command::Init {
+            database,
+            schema,
+            schema_pattern,
+            role,
+            metadata,
+            migrate_metadata,
+            report_extension_owner,
+            on_missing_role,
+            preset,
+            migrator_role,
+            runtime_role,
+            readonly_role,
+            dry_run,
+            ..
+        } => {
             // Resolve database name from --database flag or PGDATABASE env var
             let resolved_database = database.or_else(|| conn_config.dbname.clone())
                 .ok_or_else(|| anyhow::anyhow!(
                     "Database must be specified via --database flag or PGDATABASE environment variable"
                 ))?;
 
-            commands::init::execute(conn_config, resolved_database, schema, role, verbose).await?;
+            // clap guarantees exactly one of --schema and --schema-pattern
+            let (schema, schema_is_pattern) = match (schema, schema_pattern) {
+                (Some(schema), _) => (schema, false),
+                (None, Some(pattern)) => (pattern, true),
+                (None, None) => unreachable!(),
+            };
+
+            // Preset role names default to ones derived from the owner role
+            let preset = preset
+                .map(|name| -> Result<PresetRoles> {
+                    let defaults = PresetRoles::from_preset(&name, &role)?;
+                    Ok(PresetRoles {
+                        migrator: migrator_role.unwrap_or(defaults.migrator),
+                        runtime: runtime_role.unwrap_or(defaults.runtime),
+                        readonly: readonly_role.unwrap_or(defaults.readonly),
+                    })
+                })
+                .transpose()?;
+
+            let opts = commands::init::InitOptions {
+                database: resolved_database,
+                schema,
+                schema_is_pattern,
+                role,
+                metadata: metadata_options(metadata),
+                migrate_metadata,
+                mapping: MappingOptions {
+                    report_extension_owner,
+                    on_missing_role: on_missing_role.as_deref().map(MissingRolePolicy::from_str).transpose()?,
+                },
+                preset,
+                dry_run,
+            };
+
+            commands::init::execute(conn_config, opts, verbose).await?;
         }
-        Command::ListMappings => {
-            commands::list_mappings::execute(conn_config, verbose).await?;
+        Command::ListMappings { include_templates } => {
+            commands::list_mappings::execute(conn_config, include_templates, verbose).await?;
         }
-        Command::Version => {
-            println!("{}", env!("CARGO_PKG_VERSION"));
+        Command::InitTemplate { template, metadata, migrate_metadata, dry_run } => {
+            let opts = commands::init_template::InitTemplateOptions {
+                template,
+                metadata: metadata_options(metadata),
+                migrate_metadata,
+                dry_run,
+            };
+            commands::init_template::execute(conn_config, opts, verbose).await?;
+        }
+        Command::SyncDefaultPrivileges { database, schema } => {
+            let resolved_database = database.or_else(|| conn_config.dbname.clone())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Database must be specified via --database flag or PGDATABASE environment variable"
+                ))?;
+
+            commands::sync_default_privileges::execute(conn_config, resolved_database, schema, verbose).await?;
+        }
+        Command::Doctor { database } => {
+            commands::doctor::execute(conn_config, database, verbose).await?;
+        }
+        Command::Verify { database, schema, object_types } => {
+            let filters = commands::verify::VerifyFilters { database, schema, object_types };
+            commands::verify::execute(conn_config, filters, verbose).await?;
+        }
+        Command::Reconcile { database, schema, object_types, dry_run, batch_size, lock_timeout } => {
+            let resolved_database = database.or_else(|| conn_config.dbname.clone())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Database must be specified via --database flag or PGDATABASE environment variable"
+                ))?;
+
+            let opts = commands::reconcile::ReconcileOptions {
+                database: resolved_database,
+                schema,
+                object_types,
+                dry_run,
+                batch_size,
+                lock_timeout,
+            };
+            commands::reconcile::execute(conn_config, opts, verbose).await?;
         }
+        Command::Remap { database, schema, from, to, dry_run, yes } => {
+            let resolved_database = database.or_else(|| conn_config.dbname.clone())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Database must be specified via --database flag or PGDATABASE environment variable"
+                ))?;
+
+            let opts = commands::remap::RemapOptions {
+                database: resolved_database,
+                schema,
+                from,
+                to,
+                dry_run,
+                yes,
+            };
+            commands::remap::execute(conn_config, opts, verbose).await?;
+        }
+        Command::CreateLogin { database, name, member_of, set_role, connection_limit, valid_until } => {
+            let opts = commands::create_login::CreateLoginOptions {
+                database: database.or_else(|| conn_config.dbname.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Database must be specified via --database flag or PGDATABASE environment variable"
+                    ))?,
+                name,
+                member_of,
+                set_role,
+                connection_limit,
+                valid_until,
+            };
+            commands::create_login::execute(conn_config, opts, verbose).await?;
+        }
+        Command::GrantMember { database, schema, member, inherit, set, admin, dry_run } => {
+            let opts = commands::member::MemberOptions {
+                database: database.or_else(|| conn_config.dbname.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Database must be specified via --database flag or PGDATABASE environment variable"
+                    ))?,
+                schema,
+                member,
+                membership: MembershipOptions { inherit, set, admin },
+                dry_run,
+            };
+            commands::member::grant(conn_config, opts, verbose).await?;
+        }
+        Command::RevokeMember { database, schema, member, dry_run } => {
+            let opts = commands::member::MemberOptions {
+                database: database.or_else(|| conn_config.dbname.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Database must be specified via --database flag or PGDATABASE environment variable"
+                    ))?,
+                schema,
+                member,
+                membership: MembershipOptions::default(),
+                dry_run,
+            };
+            commands::member::revoke(conn_config, opts, verbose).await?;
+        }
+        Command::Apply { manifest, prune, check, migrate_metadata, yes } => {
+            let opts = commands::apply::ApplyOptions { manifest, prune, check, migrate_metadata, yes };
+            let result = commands::apply::execute(conn_config, opts, verbose).await;
+            if check {
+                exit_with_drift_status(result);
+            }
+            result?;
+        }
+        Command::ListObjects { database, schema, format } => {
+            let json = match format.as_str() {
+                "table" => false,
+                "json" => true,
+                other => anyhow::bail!("Unknown format '{}'; use table or json", other),
+            };
+            let opts = commands::list_objects::ListObjectsOptions { database, schema, json };
+            commands::list_objects::execute(conn_config, opts, verbose).await?;
+        }
+        Command::Diff { left, right, format } => {
+            let json = match format.as_str() {
+                "text" => false,
+                "json" => true,
+                other => anyhow::bail!("Unknown format '{}'; use text or json", other),
+            };
+            let opts = commands::diff::DiffOptions { left, right, json };
+            exit_with_drift_status(commands::diff::execute(conn_config, opts, verbose).await);
+        }
+        Command::Export { output, format } => {
+            let opts = commands::export::ExportOptions { output, format };
+            commands::export::execute(conn_config, opts, verbose).await?;
+        }
+        Command::WhoCan { database, schema } => {
+            let opts = commands::who_can::WhoCanOptions {
+                database: database.or_else(|| conn_config.dbname.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Database must be specified via --database flag or PGDATABASE environment variable"
+                    ))?,
+                schema,
+            };
+            commands::who_can::execute(conn_config, opts, verbose).await?;
+        }
+        Command::Exec { database, schema, command } => {
+            let opts = commands::exec::ExecOptions {
+                database: database.or_else(|| conn_config.dbname.clone())
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "Database must be specified via --database flag or PGDATABASE environment variable"
+                    ))?,
+                schema,
+                command,
+            };
+            let code = commands::exec::execute(conn_config, opts, verbose).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Command::Import { manifest } => {
+            let opts = commands::import::ImportOptions { manifest };
+            commands::import::execute(conn_config, opts, verbose).await?;
+        }
+        Command::Uninstall { database, schema, revoke_grants, drop_schema, drop_role, yes } => {
+            let resolved_database = database.or_else(|| conn_config.dbname.clone())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "Database must be specified via --database flag or PGDATABASE environment variable"
+                ))?;
+
+            let opts = commands::uninstall::UninstallOptions {
+                database: resolved_database,
+                schema,
+                revoke_grants,
+                drop_schema,
+                drop_role,
+                yes,
+            };
+            commands::uninstall::execute(conn_config, opts, verbose).await?;
+        }
+        Command::GenerateSql { .. } | Command::Version => unreachable!("handled before connecting"),
     }
 
     Ok(())
 }
+
+/// Exit status of the commands that report drift: 0 when in sync, 1 when they
+/// found differences, 2 when they could not tell because of an error.
+fn exit_with_drift_status(result: Result<bool>) -> ! {
+    match result {
+        Ok(true) => std::process::exit(0),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn metadata_options(args: MetadataArgs) -> commands::init::MetadataOptions {
+    commands::init::MetadataOptions {
+        schema: args.metadata_schema,
+        config_table: args.config_table,
+        function: args.function_name,
+        event_trigger: args.trigger_name,
+        cleanup_function: args.cleanup_function_name,
+        cleanup_event_trigger: args.cleanup_trigger_name,
+    }
+}
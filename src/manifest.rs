@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::BLOCKED_DATABASES;
+use crate::commands::init::MetadataOptions;
+use crate::sql_templates::{MappingOptions, MetadataNames, MissingRolePolicy, PresetRoles};
+
+/// Desired schema-to-role mappings, kept in version control and read by `apply`
+/// and `import`; written by `export`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub databases: Vec<DatabaseManifest>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseManifest {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MetadataManifest>,
+    #[serde(default)]
+    pub mappings: Vec<MappingManifest>,
+}
+
+/// Metadata location, as given to `init` with `--metadata-schema` and the name
+/// options; unset names keep the existing installation's, or the default.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_table: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_trigger: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup_function: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup_event_trigger: Option<String>,
+}
+
+impl MetadataManifest {
+    /// Names that differ from the defaults, or `None` for a default installation.
+    pub fn from_names(names: &MetadataNames) -> Option<Self> {
+        let default = MetadataNames::default();
+        let differs = |value: &String, default: &String| (value != default).then(|| value.clone());
+        let manifest = Self {
+            schema: differs(&names.schema, &default.schema),
+            config_table: differs(&names.config_table, &default.config_table),
+            function: differs(&names.function, &default.function),
+            event_trigger: differs(&names.event_trigger, &default.event_trigger),
+            cleanup_function: differs(&names.cleanup_function, &default.cleanup_function),
+            cleanup_event_trigger: differs(&names.cleanup_event_trigger, &default.cleanup_event_trigger),
+        };
+        (*names != default).then_some(manifest)
+    }
+
+    pub fn options(&self) -> MetadataOptions {
+        MetadataOptions {
+            schema: self.schema.clone(),
+            config_table: self.config_table.clone(),
+            function: self.function.clone(),
+            event_trigger: self.event_trigger.clone(),
+            cleanup_function: self.cleanup_function.clone(),
+            cleanup_event_trigger: self.cleanup_event_trigger.clone(),
+        }
+    }
+}
+
+impl DatabaseManifest {
+    pub fn metadata_options(&self) -> MetadataOptions {
+        self.metadata.as_ref().map(MetadataManifest::options).unwrap_or_default()
+    }
+}
+
+/// One mapping; options left out keep whatever the server has, as with `init`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappingManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_pattern: Option<String>,
+    pub role: String,
+    /// Manifests exported before the rename used `transfer_extension_owner`
+    #[serde(alias = "transfer_extension_owner", skip_serializing_if = "Option::is_none")]
+    pub report_extension_owner: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_missing_role: Option<String>,
+    /// As `init --preset`; the role names below default to ones derived from `role`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrator_role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime_role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readonly_role: Option<String>,
+    /// When the mapping was created and last changed on the exporting server;
+    /// informational only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+impl MappingManifest {
+    /// Schema name or LIKE pattern, and whether it is a pattern. Only valid after
+    /// `load` has checked that exactly one of the two is set.
+    pub fn schema_name(&self) -> (&str, bool) {
+        match (&self.schema, &self.schema_pattern) {
+            (Some(schema), _) => (schema, false),
+            (None, Some(pattern)) => (pattern, true),
+            (None, None) => unreachable!("validated in load"),
+        }
+    }
+
+    pub fn options(&self) -> Result<MappingOptions> {
+        Ok(MappingOptions {
+            report_extension_owner: self.report_extension_owner,
+            on_missing_role: self.on_missing_role.as_deref().map(MissingRolePolicy::from_str).transpose()?,
+        })
+    }
+
+    /// Preset roles, resolved the way `init --preset` resolves them.
+    pub fn preset_roles(&self) -> Result<Option<PresetRoles>> {
+        let Some(name) = &self.preset else {
+            if self.migrator_role.is_some() || self.runtime_role.is_some() || self.readonly_role.is_some() {
+                anyhow::bail!("'migrator_role', 'runtime_role' and 'readonly_role' need 'preset'");
+            }
+            return Ok(None);
+        };
+        let defaults = PresetRoles::from_preset(name, &self.role)?;
+        Ok(Some(PresetRoles {
+            migrator: self.migrator_role.clone().unwrap_or(defaults.migrator),
+            runtime: self.runtime_role.clone().unwrap_or(defaults.runtime),
+            readonly: self.readonly_role.clone().unwrap_or(defaults.readonly),
+        }))
+    }
+
+    /// Sets the preset fields for roles recorded on a server, naming only the
+    /// roles that differ from the preset's defaults.
+    pub fn with_preset_roles(mut self, roles: Option<&PresetRoles>) -> Self {
+        let Some(roles) = roles else {
+            return self;
+        };
+        let defaults = PresetRoles::from_preset("standard", &self.role).expect("standard is a preset");
+        let differs = |value: &String, default: &String| (value != default).then(|| value.clone());
+        self.preset = Some("standard".to_string());
+        self.migrator_role = differs(&roles.migrator, &defaults.migrator);
+        self.runtime_role = differs(&roles.runtime, &defaults.runtime);
+        self.readonly_role = differs(&roles.readonly, &defaults.readonly);
+        self
+    }
+}
+
+/// Reads a TOML (`.toml`) or YAML (`.yaml`, `.yml`) manifest and checks it.
+pub fn load(path: &Path) -> Result<Manifest> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let manifest = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => parse_toml(&content),
+        Some("yaml" | "yml") => parse_yaml(&content),
+        _ => anyhow::bail!("Unknown manifest format for {}; use a .toml, .yaml or .yml file", path.display()),
+    }
+    .with_context(|| format!("Failed to parse {}", path.display()))?;
+    validate(&manifest)?;
+    Ok(manifest)
+}
+
+/// Renders a manifest as TOML, or as YAML when `yaml` is set.
+pub fn render(manifest: &Manifest, yaml: bool) -> Result<String> {
+    if yaml {
+        serde_yaml::to_string(manifest).context("Failed to render YAML manifest")
+    } else {
+        toml::to_string(manifest).context("Failed to render TOML manifest")
+    }
+}
+
+fn parse_toml(content: &str) -> Result<Manifest> {
+    Ok(toml::from_str(content)?)
+}
+
+fn parse_yaml(content: &str) -> Result<Manifest> {
+    Ok(serde_yaml::from_str(content)?)
+}
+
+fn validate(manifest: &Manifest) -> Result<()> {
+    let mut seen = HashSet::new();
+    for database in &manifest.databases {
+        if BLOCKED_DATABASES.contains(&database.name.as_str()) {
+            anyhow::bail!("Database '{}' is a system database and cannot be managed", database.name);
+        }
+        for mapping in &database.mappings {
+            let (schema, is_pattern) = match (&mapping.schema, &mapping.schema_pattern) {
+                (Some(schema), None) => (schema, false),
+                (None, Some(pattern)) => (pattern, true),
+                _ => anyhow::bail!(
+                    "Each mapping in database '{}' needs exactly one of 'schema' and 'schema_pattern' (role '{}')",
+                    database.name, mapping.role
+                ),
+            };
+            if is_pattern && !schema.contains(['%', '_']) {
+                anyhow::bail!("Schema pattern '{}' contains no LIKE wildcard ('%' or '_')", schema);
+            }
+            mapping.options()
+                .with_context(|| format!("Invalid options for {}.{}", database.name, schema))?;
+            if mapping.preset_roles()
+                .with_context(|| format!("Invalid preset for {}.{}", database.name, schema))?
+                .is_some()
+                && is_pattern
+            {
+                anyhow::bail!("Schema pattern '{}' cannot have a preset; schemas matching a pattern only get an owner", schema);
+            }
+            if !seen.insert((database.name.as_str(), schema.as_str())) {
+                anyhow::bail!("{}.{} is listed more than once", database.name, schema);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_yaml_read_the_same_manifest() {
+        let toml = r#"
+            [[databases]]
+            name = "app_prod"
+
+            [[databases.mappings]]
+            schema = "app"
+            role = "app_owner"
+            on_missing_role = "error"
+
+            [[databases.mappings]]
+            schema_pattern = "tenant_%"
+            role = "tenant_owner"
+        "#;
+        let yaml = "
+databases:
+  - name: app_prod
+    mappings:
+      - schema: app
+        role: app_owner
+        on_missing_role: error
+      - schema_pattern: tenant_%
+        role: tenant_owner
+";
+        for manifest in [parse_toml(toml).unwrap(), parse_yaml(yaml).unwrap()] {
+            validate(&manifest).unwrap();
+            let mappings = &manifest.databases[0].mappings;
+            assert_eq!(mappings[0].schema_name(), ("app", false));
+            assert_eq!(mappings[0].options().unwrap().on_missing_role, Some(MissingRolePolicy::Error));
+            assert_eq!(mappings[1].schema_name(), ("tenant_%", true));
+        }
+    }
+
+    #[test]
+    fn validate_rejects_duplicates_and_ambiguous_schemas() {
+        let duplicate = parse_toml(
+            r#"
+            [[databases]]
+            name = "db"
+            mappings = [{ schema = "app", role = "a" }, { schema = "app", role = "b" }]
+            "#,
+        )
+        .unwrap();
+        assert!(validate(&duplicate).is_err());
+
+        let both = parse_toml(
+            r#"
+            [[databases]]
+            name = "db"
+            mappings = [{ schema = "app", schema_pattern = "app_%", role = "a" }]
+            "#,
+        )
+        .unwrap();
+        assert!(validate(&both).is_err());
+    }
+
+    #[test]
+    fn preset_roles_default_from_the_owner_and_round_trip() {
+        let manifest = parse_toml(
+            r#"
+            [[databases]]
+            name = "db"
+            mappings = [{ schema = "app", role = "app_owner", preset = "standard", runtime_role = "app_rw" }]
+            "#,
+        )
+        .unwrap();
+        validate(&manifest).unwrap();
+        let mapping = &manifest.databases[0].mappings[0];
+        let roles = mapping.preset_roles().unwrap().unwrap();
+        assert_eq!(roles.migrator, "app_owner_migrator");
+        assert_eq!(roles.runtime, "app_rw");
+
+        // Exported back, only the role that differs from the default is named
+        let exported = MappingManifest {
+            schema: Some("app".to_string()),
+            schema_pattern: None,
+            role: "app_owner".to_string(),
+            report_extension_owner: None,
+            on_missing_role: None,
+            preset: None,
+            migrator_role: None,
+            runtime_role: None,
+            readonly_role: None,
+            created_at: None,
+            updated_at: None,
+        }
+        .with_preset_roles(Some(&roles));
+        assert_eq!(exported.preset.as_deref(), Some("standard"));
+        assert_eq!(exported.migrator_role, None);
+        assert_eq!(exported.runtime_role.as_deref(), Some("app_rw"));
+
+        let pattern = parse_toml(
+            r#"
+            [[databases]]
+            name = "db"
+            mappings = [{ schema_pattern = "tenant_%", role = "t", preset = "standard" }]
+            "#,
+        )
+        .unwrap();
+        assert!(validate(&pattern).is_err());
+    }
+}
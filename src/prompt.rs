@@ -0,0 +1,47 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::{Context, Result};
+
+/// Asks a yes/no question on the terminal. `assume_yes` (from `--yes`) answers
+/// without asking. Without a terminal there is nobody to ask, so the command
+/// stops rather than guessing.
+pub fn confirm(question: &str, assume_yes: bool) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        anyhow::bail!("{} Re-run with --yes to confirm non-interactively.", question);
+    }
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush().context("Failed to write prompt")?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)
+        .context("Failed to read answer")?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Reads a new password: typed twice on a terminal without echo, or the first
+/// line of stdin when it is not a terminal (for scripts).
+pub fn new_password(user: &str) -> Result<String> {
+    let password = if io::stdin().is_terminal() {
+        let password = rpassword::prompt_password(format!("Password for '{}': ", user))
+            .context("Failed to read password")?;
+        let again = rpassword::prompt_password("Repeat password: ")
+            .context("Failed to read password")?;
+        if password != again {
+            anyhow::bail!("Passwords do not match");
+        }
+        password
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)
+            .context("Failed to read password from stdin")?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        anyhow::bail!("Password must not be empty");
+    }
+    Ok(password)
+}
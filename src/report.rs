@@ -6,6 +6,8 @@ pub enum ActionOutcome {
     Created,
     Skipped,
     Updated,
+    Removed,
+    Failed,
 }
 
 impl fmt::Display for ActionOutcome {
@@ -14,6 +16,8 @@ impl fmt::Display for ActionOutcome {
             ActionOutcome::Created => write!(f, "Created"),
             ActionOutcome::Skipped => write!(f, "Skipped"),
             ActionOutcome::Updated => write!(f, "Updated"),
+            ActionOutcome::Removed => write!(f, "Removed"),
+            ActionOutcome::Failed => write!(f, "Failed"),
         }
     }
 }
@@ -45,6 +49,8 @@ impl ActionReport {
         let created = self.actions.iter().filter(|(_, o)| matches!(o, ActionOutcome::Created)).count();
         let skipped = self.actions.iter().filter(|(_, o)| matches!(o, ActionOutcome::Skipped)).count();
         let updated = self.actions.iter().filter(|(_, o)| matches!(o, ActionOutcome::Updated)).count();
+        let removed = self.actions.iter().filter(|(_, o)| matches!(o, ActionOutcome::Removed)).count();
+        let failed = self.actions.iter().filter(|(_, o)| matches!(o, ActionOutcome::Failed)).count();
 
         println!();
         println!("=== {} Summary ===", self.command_name);
@@ -59,7 +65,173 @@ impl ActionReport {
         if updated > 0 {
             println!("  Updated: {}", updated);
         }
+        if removed > 0 {
+            println!("  Removed: {}", removed);
+        }
+        if failed > 0 {
+            println!("  Failed: {}", failed);
+        }
+
+        println!("==================");
+    }
+}
+
+/// One step of a plan: what it does, the outcome it will have, and its SQL
+#[derive(Debug)]
+pub struct PlannedAction {
+    pub description: String,
+    pub outcome: ActionOutcome,
+    pub statements: Vec<String>,
+}
+
+/// Ordered actions worked out before anything is changed, so they can be shown
+/// in full and then applied together
+#[derive(Debug)]
+pub struct Plan {
+    title: String,
+    actions: Vec<PlannedAction>,
+}
+
+impl Plan {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, description: impl Into<String>, outcome: ActionOutcome, statements: Vec<String>) {
+        self.actions.push(PlannedAction {
+            description: description.into(),
+            outcome,
+            statements,
+        });
+    }
+
+    pub fn actions(&self) -> &[PlannedAction] {
+        &self.actions
+    }
+
+    /// Print every action with the SQL it will run. Function bodies are only
+    /// shown in full at `-vv`, as with `[SQL]` logging.
+    pub fn print(&self, verbose: u8) {
+        println!("=== {} ===", self.title);
+        for (index, action) in self.actions.iter().enumerate() {
+            println!("{:>3}. {}: {}", index + 1, action.outcome, action.description);
+            for sql in &action.statements {
+                match sql.lines().next() {
+                    Some(first_line) if verbose < 2 && is_function_body(sql) => {
+                        println!("       {} ... (-vv shows the function body)", first_line)
+                    }
+                    _ => println!("       {}", sql.replace('\n', "\n       ")),
+                }
+            }
+        }
+        println!();
+    }
+}
+
+/// Whether `sql` defines a function; such statements are logged only at `-vv`
+pub fn is_function_body(sql: &str) -> bool {
+    sql.contains("$$")
+}
+
+/// Result of an individual health check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
 
+/// Collects check results, printing each with its remediation hint
+#[derive(Debug)]
+pub struct CheckReport {
+    command_name: String,
+    checks: Vec<(String, CheckStatus)>,
+}
+
+impl CheckReport {
+    pub fn new(command_name: impl Into<String>) -> Self {
+        Self {
+            command_name: command_name.into(),
+            checks: Vec::new(),
+        }
+    }
+
+    /// Print a heading for the checks that follow
+    pub fn section(&self, title: impl AsRef<str>) {
+        println!();
+        println!("--- {} ---", title.as_ref());
+    }
+
+    pub fn pass(&mut self, description: impl Into<String>) {
+        self.record(description, CheckStatus::Pass, None);
+    }
+
+    pub fn warn(&mut self, description: impl Into<String>, hint: impl AsRef<str>) {
+        self.record(description, CheckStatus::Warn, Some(hint.as_ref()));
+    }
+
+    pub fn fail(&mut self, description: impl Into<String>, hint: impl AsRef<str>) {
+        self.record(description, CheckStatus::Fail, Some(hint.as_ref()));
+    }
+
+    /// Record a check with immediate console output
+    fn record(&mut self, description: impl Into<String>, status: CheckStatus, hint: Option<&str>) {
+        let desc = description.into();
+        println!("[{}] {}", status, desc);
+        if let Some(hint) = hint {
+            println!("       Hint: {}", hint);
+        }
+        self.checks.push((desc, status));
+    }
+
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|(_, s)| *s == status).count()
+    }
+
+    /// Print the summary report
+    pub fn print_summary(&self) {
+        println!();
+        println!("=== {} Summary ===", self.command_name);
+        println!("Total checks: {}", self.checks.len());
+        println!("  Passed: {}", self.count(CheckStatus::Pass));
+        println!("  Warnings: {}", self.count(CheckStatus::Warn));
+        println!("  Failed: {}", self.count(CheckStatus::Fail));
         println!("==================");
     }
 }
+
+/// Shortens `s` to at most `max_len` characters for a table column, marking the cut with `[...]`.
+pub fn truncate_with_ellipsis(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        s.to_string()
+    } else {
+        format!("{}[...]", s.chars().take(max_len.saturating_sub(5)).collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_with_ellipsis_counts_characters() {
+        assert_eq!(truncate_with_ellipsis("app", 10), "app");
+        assert_eq!(truncate_with_ellipsis("abcdefghijkl", 10), "abcde[...]");
+        // Multibyte names are cut on character boundaries, not bytes
+        assert_eq!(truncate_with_ellipsis("zäöü", 4), "zäöü");
+        assert_eq!(truncate_with_ellipsis("schéma_größe", 10), "schém[...]");
+    }
+}
@@ -1,121 +1,505 @@
+use crate::db::ServerCapabilities;
+
+/// Comments attached to installed objects so other commands can find them
+/// regardless of the schema or names chosen at `init` time.
+pub const CONFIG_TABLE_MARKER: &str = "pg-app-role-manager: schema ownership config";
+pub const TRIGGER_FUNCTION_MARKER: &str = "pg-app-role-manager: ownership transfer function";
+pub const CLEANUP_FUNCTION_MARKER: &str = "pg-app-role-manager: dropped schema cleanup function";
+
+/// Trigger fragment applying the mapping's `on_missing_role` policy when the
+/// target role no longer exists.
+const MISSING_ROLE_CHECK: &str = r#"CASE missing_role_policy
+                    WHEN 'error' THEN
+                        RAISE EXCEPTION 'Target role "%" does not exist; cannot transfer ownership of %',
+                                        target_role_name, obj.object_identity
+                            USING HINT = 'Recreate the role or change the on_missing_role setting of the mapping';
+                    WHEN 'warn' THEN
+                        RAISE WARNING 'Target role "%" does not exist; ownership of % was not transferred',
+                                      target_role_name, obj.object_identity;
+                    ELSE
+                        NULL;
+                END CASE;"#;
+
+/// Condition selecting the config rows that apply to the schema named by
+/// `schema_expr` (config table aliased `c`). Order candidates by
+/// [`MAPPING_PRECEDENCE`] and take the first.
+pub fn mapping_match_condition(schema_expr: &str) -> String {
+    format!(
+        "c.active AND ((NOT c.is_pattern AND c.schema_name = {e}) OR (c.is_pattern AND {e} LIKE c.schema_name))",
+        e = schema_expr
+    )
+}
+
+/// Exact mappings win over patterns; among patterns the longest (most specific) wins.
+pub const MAPPING_PRECEDENCE: &str = "c.is_pattern, length(c.schema_name) DESC, c.schema_name";
+
+/// Config table columns added after its initial release, in the order `init`
+/// adds them.
+pub const ADDED_CONFIG_COLUMNS: &[(&str, &str)] = &[
+    ("report_extension_owner", "boolean NOT NULL DEFAULT false"),
+    ("active", "boolean NOT NULL DEFAULT true"),
+    ("dropped_at", "timestamptz"),
+    ("dropped_by", "name"),
+    ("on_missing_role", "text NOT NULL DEFAULT 'warn' CHECK (on_missing_role IN ('ignore', 'warn', 'error'))"),
+    ("is_pattern", "boolean NOT NULL DEFAULT false"),
+    ("migrator_role", "name"),
+    ("runtime_role", "name"),
+    ("readonly_role", "name"),
+    ("default_privilege_grantors", "name[]"),
+];
+
+/// Config table columns renamed after their release, as `(old, new)`. `init`
+/// renames them before adding [`ADDED_CONFIG_COLUMNS`], so stored values carry over.
+pub const RENAMED_CONFIG_COLUMNS: &[(&str, &str)] = &[
+    ("transfer_extension_owner", "report_extension_owner"),
+];
+
+/// Location and names of the objects the tool installs in each database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataNames {
+    pub schema: String,
+    pub config_table: String,
+    pub function: String,
+    pub event_trigger: String,
+    pub cleanup_function: String,
+    pub cleanup_event_trigger: String,
+}
+
+impl Default for MetadataNames {
+    fn default() -> Self {
+        Self {
+            schema: "public".to_string(),
+            config_table: "schema_ownership_config".to_string(),
+            function: "auto_transfer_schema_ownership".to_string(),
+            event_trigger: "auto_transfer_schema_ownership_trigger".to_string(),
+            cleanup_function: "schema_ownership_drop_cleanup".to_string(),
+            cleanup_event_trigger: "schema_ownership_drop_cleanup_trigger".to_string(),
+        }
+    }
+}
+
+impl MetadataNames {
+    pub fn qualified_config_table(&self) -> String {
+        format!("{}.{}", quote_identifier(&self.schema), quote_identifier(&self.config_table))
+    }
+
+    pub fn qualified_function(&self) -> String {
+        format!("{}.{}", quote_identifier(&self.schema), quote_identifier(&self.function))
+    }
+
+    pub fn qualified_cleanup_function(&self) -> String {
+        format!("{}.{}", quote_identifier(&self.schema), quote_identifier(&self.cleanup_function))
+    }
+
+    /// Change history for the config table, kept next to it.
+    pub fn history_table(&self) -> String {
+        format!("{}_history", self.config_table)
+    }
+
+    pub fn qualified_history_table(&self) -> String {
+        format!("{}.{}", quote_identifier(&self.schema), quote_identifier(&self.history_table()))
+    }
+}
+
+/// Per-mapping settings stored alongside the schema-to-role mapping. Unset
+/// options leave the stored value (or the column default) unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct MappingOptions {
+    pub report_extension_owner: Option<bool>,
+    pub on_missing_role: Option<MissingRolePolicy>,
+}
+
+/// What the event trigger does when a mapping's target role has been dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MissingRolePolicy {
+    Ignore,
+    Warn,
+    Error,
+}
+
+impl MissingRolePolicy {
+    /// The column default, also assumed for config tables that predate the column.
+    pub const DEFAULT: MissingRolePolicy = MissingRolePolicy::Warn;
+
+    pub fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ignore" => Ok(MissingRolePolicy::Ignore),
+            "warn" => Ok(MissingRolePolicy::Warn),
+            "error" => Ok(MissingRolePolicy::Error),
+            _ => Err(anyhow::anyhow!(
+                "Invalid missing role policy '{}'. Valid options are: ignore, warn, error.",
+                s
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MissingRolePolicy::Ignore => "ignore",
+            MissingRolePolicy::Warn => "warn",
+            MissingRolePolicy::Error => "error",
+        }
+    }
+}
+
+/// Options of a membership granted by `grant-member`; unset ones keep the
+/// existing grant's value, or the server default for a new grant.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MembershipOptions {
+    /// PostgreSQL 16 and later
+    pub inherit: Option<bool>,
+    /// PostgreSQL 16 and later
+    pub set: Option<bool>,
+    pub admin: Option<bool>,
+}
+
+impl MembershipOptions {
+    /// The options as written after `WITH`, e.g. `INHERIT FALSE, ADMIN TRUE`.
+    pub fn describe(&self) -> String {
+        [("INHERIT", self.inherit), ("SET", self.set), ("ADMIN", self.admin)]
+            .iter()
+            .filter_map(|(name, value)| value.map(|v| format!("{} {}", name, if v { "TRUE" } else { "FALSE" })))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Companion roles `init --preset` creates next to the NOLOGIN owner role.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PresetRoles {
+    /// May SET ROLE to the owner to run migrations, but does not inherit its privileges
+    pub migrator: String,
+    /// Reads and writes data
+    pub runtime: String,
+    pub readonly: String,
+}
+
+impl PresetRoles {
+    /// Role names for a preset, derived from the owner role. `standard` (migrator,
+    /// runtime and read-only) is the only preset so far.
+    pub fn from_preset(name: &str, owner: &str) -> anyhow::Result<Self> {
+        match name.to_lowercase().as_str() {
+            "standard" => Ok(PresetRoles {
+                migrator: format!("{}_migrator", owner),
+                runtime: format!("{}_runtime", owner),
+                readonly: format!("{}_readonly", owner),
+            }),
+            _ => Err(anyhow::anyhow!("Invalid preset '{}'. Valid options are: standard.", name)),
+        }
+    }
+}
+
+/// What a preset role may do with the objects in a schema.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchemaAccess {
+    ReadWrite,
+    ReadOnly,
+}
+
+impl SchemaAccess {
+    /// Privileges per object kind, as written in `GRANT ... ON ALL <kind>`. Read-write
+    /// access includes `MAINTAIN` where the server has it; read-only access never
+    /// does, since it also allows `LOCK TABLE`.
+    fn privileges(&self, capabilities: &ServerCapabilities) -> Vec<(&'static str, &'static str)> {
+        match self {
+            SchemaAccess::ReadWrite => vec![
+                (
+                    "TABLES",
+                    if capabilities.has_maintain_privilege() {
+                        "SELECT, INSERT, UPDATE, DELETE, MAINTAIN"
+                    } else {
+                        "SELECT, INSERT, UPDATE, DELETE"
+                    },
+                ),
+                ("SEQUENCES", "USAGE, SELECT, UPDATE"),
+                ("FUNCTIONS", "EXECUTE"),
+            ],
+            SchemaAccess::ReadOnly => vec![("TABLES", "SELECT"), ("SEQUENCES", "SELECT")],
+        }
+    }
+}
+
 pub struct SqlTemplates {
     pub database: String,
     pub schema: String,
     pub role: String,
+    pub metadata: MetadataNames,
+    pub options: MappingOptions,
+    pub capabilities: ServerCapabilities,
+    /// `schema` is a LIKE pattern matched against schema names by the trigger
+    pub schema_is_pattern: bool,
 }
 
 impl SqlTemplates {
-    pub fn new(database: String, schema: String, role: String) -> Self {
+    pub fn new(
+        database: String,
+        schema: String,
+        role: String,
+        metadata: MetadataNames,
+        capabilities: ServerCapabilities,
+    ) -> Self {
         Self {
             database,
             schema,
             role,
+            metadata,
+            options: MappingOptions::default(),
+            capabilities,
+            schema_is_pattern: false,
         }
     }
 
     pub fn create_database(&self) -> String {
-        format!("CREATE DATABASE {}", self.quote_identifier(&self.database))
+        format!("CREATE DATABASE {}", quote_identifier(&self.database))
+    }
+
+    /// Lets users with CREATEDB copy the database with `CREATE DATABASE ... TEMPLATE`.
+    pub fn mark_template(&self) -> String {
+        format!("ALTER DATABASE {} IS_TEMPLATE true", quote_identifier(&self.database))
+    }
+
+    /// Applies the PostgreSQL 15 default for the `public` schema to a database
+    /// created on an older server. Returns `None` where it is already the default.
+    pub fn revoke_public_schema_create(&self) -> Option<String> {
+        if self.capabilities.public_schema_create_restricted() {
+            None
+        } else {
+            Some("REVOKE CREATE ON SCHEMA public FROM PUBLIC".to_string())
+        }
     }
 
     pub fn create_schema(&self) -> String {
-        format!("CREATE SCHEMA {}", self.quote_identifier(&self.schema))
+        format!("CREATE SCHEMA {}", quote_identifier(&self.schema))
     }
 
     pub fn create_role(&self) -> String {
-        format!("CREATE ROLE {} NOLOGIN", self.quote_identifier(&self.role))
+        format!("CREATE ROLE {} NOLOGIN", quote_identifier(&self.role))
     }
 
     pub fn grant_connect(&self) -> String {
         format!(
             "GRANT CONNECT ON DATABASE {} TO {}",
-            self.quote_identifier(&self.database),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.database),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn alter_schema_owner(&self) -> String {
         format!(
             "ALTER SCHEMA {} OWNER TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn grant_schema_usage(&self) -> String {
         format!(
             "GRANT USAGE ON SCHEMA {} TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn grant_schema_create(&self) -> String {
         format!(
             "GRANT CREATE ON SCHEMA {} TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn grant_all_tables(&self) -> String {
         format!(
             "GRANT ALL PRIVILEGES ON ALL TABLES IN SCHEMA {} TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn grant_all_sequences(&self) -> String {
         format!(
             "GRANT ALL PRIVILEGES ON ALL SEQUENCES IN SCHEMA {} TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn grant_all_functions(&self) -> String {
         format!(
             "GRANT ALL PRIVILEGES ON ALL FUNCTIONS IN SCHEMA {} TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn alter_default_privileges_tables(&self) -> String {
         format!(
             "ALTER DEFAULT PRIVILEGES IN SCHEMA {} GRANT ALL PRIVILEGES ON TABLES TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn alter_default_privileges_sequences(&self) -> String {
         format!(
             "ALTER DEFAULT PRIVILEGES IN SCHEMA {} GRANT ALL PRIVILEGES ON SEQUENCES TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
         )
     }
 
     pub fn alter_default_privileges_functions(&self) -> String {
         format!(
             "ALTER DEFAULT PRIVILEGES IN SCHEMA {} GRANT ALL PRIVILEGES ON FUNCTIONS TO {}",
-            self.quote_identifier(&self.schema),
-            self.quote_identifier(&self.role)
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
+        )
+    }
+
+    /// Default privileges for objects created by `grantor` (a member of the target
+    /// role, or the target role itself) rather than by the role running `init`.
+    pub fn alter_default_privileges_for_role(&self, grantor: &str) -> Vec<String> {
+        ["TABLES", "SEQUENCES", "FUNCTIONS"]
+            .iter()
+            .map(|kind| {
+                format!(
+                    "ALTER DEFAULT PRIVILEGES FOR ROLE {} IN SCHEMA {} GRANT ALL PRIVILEGES ON {} TO {}",
+                    quote_identifier(grantor),
+                    quote_identifier(&self.schema),
+                    kind,
+                    quote_identifier(&self.role)
+                )
+            })
+            .collect()
+    }
+
+    /// Query producing `alter_default_privileges_for_role` statements for every
+    /// current (recursive) member of the role, for use where members are not
+    /// known in advance.
+    pub fn alter_default_privileges_for_members_query(&self) -> String {
+        format!(
+            "{members}
+             SELECT format('ALTER DEFAULT PRIVILEGES FOR ROLE %I IN SCHEMA %I GRANT ALL PRIVILEGES ON %s TO %I',
+                           r.rolname, {schema}, kind, {role})
+             FROM members
+             JOIN pg_roles r ON r.oid = members.member
+             CROSS JOIN unnest(ARRAY['TABLES', 'SEQUENCES', 'FUNCTIONS']) AS kind
+             ORDER BY r.rolname",
+            members = self.members_cte(),
+            role = quote_literal(&self.role),
+            schema = quote_literal(&self.schema)
         )
     }
 
-    pub fn create_config_table(&self) -> &'static str {
-        r#"CREATE TABLE IF NOT EXISTS public.schema_ownership_config (
+    /// `WITH RECURSIVE members`: the oids of every current (recursive) member of the role.
+    fn members_cte(&self) -> String {
+        format!(
+            "WITH RECURSIVE members AS (
+                 SELECT m.member
+                 FROM pg_auth_members m
+                 JOIN pg_roles g ON g.oid = m.roleid
+                 WHERE g.rolname = {}
+                 UNION
+                 SELECT m.member
+                 FROM pg_auth_members m
+                 JOIN members ON m.roleid = members.member
+             )",
+            quote_literal(&self.role)
+        )
+    }
+
+    pub fn revoke_default_privileges_for_role(&self, grantor: &str) -> Vec<String> {
+        ["TABLES", "SEQUENCES", "FUNCTIONS"]
+            .iter()
+            .map(|kind| {
+                format!(
+                    "ALTER DEFAULT PRIVILEGES FOR ROLE {} IN SCHEMA {} REVOKE ALL PRIVILEGES ON {} FROM {}",
+                    quote_identifier(grantor),
+                    quote_identifier(&self.schema),
+                    kind,
+                    quote_identifier(&self.role)
+                )
+            })
+            .collect()
+    }
+
+    pub fn create_metadata_schema(&self) -> String {
+        format!("CREATE SCHEMA IF NOT EXISTS {}", quote_identifier(&self.metadata.schema))
+    }
+
+    pub fn create_config_table(&self) -> String {
+        format!(
+            r#"CREATE TABLE IF NOT EXISTS {} (
     schema_name name PRIMARY KEY,
     target_role name NOT NULL,
     created_at timestamptz DEFAULT now(),
     updated_at timestamptz DEFAULT now()
-)"#
+)"#,
+            self.metadata.qualified_config_table()
+        )
+    }
+
+    /// Columns renamed and added to the config table after its initial release.
+    pub fn upgrade_config_table(&self) -> Vec<String> {
+        RENAMED_CONFIG_COLUMNS
+            .iter()
+            .map(|(old, new)| self.rename_config_column(old, new))
+            .chain(
+                ADDED_CONFIG_COLUMNS
+                    .iter()
+                    .map(|(column, definition)| self.add_config_column(column, definition)),
+            )
+            .collect()
     }
 
-    pub fn create_trigger_function(&self) -> &'static str {
-        r#"CREATE OR REPLACE FUNCTION auto_transfer_schema_ownership()
+    fn rename_config_column(&self, old: &str, new: &str) -> String {
+        let table = self.metadata.qualified_config_table();
+        format!(
+            r#"DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM pg_attribute WHERE attrelid = {table_literal}::regclass AND attname = {old_literal} AND NOT attisdropped)
+       AND NOT EXISTS (SELECT 1 FROM pg_attribute WHERE attrelid = {table_literal}::regclass AND attname = {new_literal} AND NOT attisdropped) THEN
+        ALTER TABLE {table} RENAME COLUMN {old} TO {new};
+    END IF;
+END $$"#,
+            table_literal = quote_literal(&table),
+            old_literal = quote_literal(old),
+            new_literal = quote_literal(new),
+        )
+    }
+
+    fn add_config_column(&self, column: &str, definition: &str) -> String {
+        format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+            self.metadata.qualified_config_table(),
+            column,
+            definition
+        )
+    }
+
+    pub fn comment_config_table(&self) -> String {
+        format!(
+            "COMMENT ON TABLE {} IS {}",
+            self.metadata.qualified_config_table(),
+            quote_literal(CONFIG_TABLE_MARKER)
+        )
+    }
+
+    pub fn create_trigger_function(&self) -> String {
+        let (routine_types, alter_procedure) = if self.capabilities.has_procedures() {
+            (
+                "'function', 'procedure'",
+                r#"
+                    WHEN 'procedure' THEN
+                        EXECUTE format('ALTER PROCEDURE %s OWNER TO %I',
+                                     obj.object_identity, target_role_name);"#,
+            )
+        } else {
+            ("'function'", "")
+        };
+
+        format!(
+            r#"CREATE OR REPLACE FUNCTION {function}()
 RETURNS event_trigger
 LANGUAGE plpgsql
 SECURITY DEFINER
@@ -125,12 +509,83 @@ DECLARE
     target_role_name name;
     target_role_oid oid;
     current_owner_oid oid;
+    object_schema name;
+    extension_schema name;
+    report_extension boolean;
+    missing_role_policy text;
 BEGIN
     FOR obj IN SELECT * FROM pg_event_trigger_ddl_commands()
     LOOP
-        SELECT target_role INTO target_role_name
-        FROM public.schema_ownership_config
-        WHERE schema_name = obj.schema_name;
+        -- CREATE EXTENSION reports the extension itself with no schema; a different
+        -- owner is only reported when the mapping for the extension's schema opts in
+        IF obj.object_type = 'extension' THEN
+            SELECT n.nspname INTO extension_schema
+            FROM pg_extension e
+            JOIN pg_namespace n ON n.oid = e.extnamespace
+            WHERE e.oid = obj.objid;
+
+            SELECT c.target_role, c.report_extension_owner, c.on_missing_role
+            INTO target_role_name, report_extension, missing_role_policy
+            FROM {config_table} c
+            WHERE {extension_match}
+            ORDER BY {precedence}
+            LIMIT 1;
+
+            IF target_role_name IS NOT NULL AND report_extension THEN
+                SELECT oid INTO target_role_oid
+                FROM pg_roles
+                WHERE rolname = target_role_name;
+
+                IF target_role_oid IS NULL THEN
+                    {missing_role_check}
+                    CONTINUE;
+                END IF;
+
+                -- There is no ALTER EXTENSION ... OWNER TO and the catalogs are not
+                -- edited behind PostgreSQL's back, so the extension is only reported
+                IF (SELECT extowner FROM pg_extension WHERE oid = obj.objid) != target_role_oid THEN
+                    RAISE NOTICE 'Extension % is owned by its creator, not by %', obj.object_identity, target_role_name
+                        USING HINT = format('Extension ownership cannot be transferred; drop it and create it again as %I (SET ROLE %I) to have it owned by the target role.',
+                                            target_role_name, target_role_name);
+                END IF;
+            END IF;
+            CONTINUE;
+        END IF;
+
+        -- Objects belonging to an extension must keep the extension owner, otherwise
+        -- ALTER EXTENSION UPDATE and pg_dump break
+        IF obj.in_extension OR EXISTS (
+            SELECT 1 FROM pg_depend
+            WHERE classid = obj.classid
+              AND objid = obj.objid
+              AND deptype = 'e'
+        ) THEN
+            CONTINUE;
+        END IF;
+
+        -- A new schema is looked up by its own name, so schemas created at runtime
+        -- that match a pattern mapping are handed to the target role. Other schema
+        -- DDL (including an explicit ALTER SCHEMA ... OWNER TO) is left alone
+        IF obj.object_type = 'schema' AND obj.command_tag <> 'CREATE SCHEMA' THEN
+            CONTINUE;
+        ELSIF obj.object_type = 'schema' THEN
+            SELECT nspname INTO object_schema
+            FROM pg_namespace
+            WHERE oid = obj.objid;
+        ELSE
+            object_schema := obj.schema_name;
+        END IF;
+
+        -- Temporary, TOAST and catalog namespaces are never managed
+        IF object_schema IS NULL OR object_schema LIKE 'pg\_%' THEN
+            CONTINUE;
+        END IF;
+
+        SELECT c.target_role, c.on_missing_role INTO target_role_name, missing_role_policy
+        FROM {config_table} c
+        WHERE {object_match}
+        ORDER BY {precedence}
+        LIMIT 1;
 
         IF target_role_name IS NOT NULL THEN
             SELECT oid INTO target_role_oid
@@ -138,6 +593,7 @@ BEGIN
             WHERE rolname = target_role_name;
 
             IF target_role_oid IS NULL THEN
+                {missing_role_check}
                 CONTINUE;
             END IF;
 
@@ -149,7 +605,7 @@ BEGIN
                     FROM pg_class
                     WHERE oid = obj.objid;
 
-                WHEN 'function' THEN
+                WHEN {routine_types} THEN
                     SELECT proowner INTO current_owner_oid
                     FROM pg_proc
                     WHERE oid = obj.objid;
@@ -159,6 +615,11 @@ BEGIN
                     FROM pg_type
                     WHERE oid = obj.objid;
 
+                WHEN 'schema' THEN
+                    SELECT nspowner INTO current_owner_oid
+                    FROM pg_namespace
+                    WHERE oid = obj.objid;
+
                 ELSE
                     -- Ignore other object types (index, trigger, etc.)
                     NULL;
@@ -191,10 +652,13 @@ BEGIN
                                      obj.object_identity, target_role_name);
                     WHEN 'function' THEN
                         EXECUTE format('ALTER FUNCTION %s OWNER TO %I',
-                                     obj.object_identity, target_role_name);
+                                     obj.object_identity, target_role_name);{alter_procedure}
                     WHEN 'type' THEN
                         EXECUTE format('ALTER TYPE %s OWNER TO %I',
                                      obj.object_identity, target_role_name);
+                    WHEN 'schema' THEN
+                        EXECUTE format('ALTER SCHEMA %I OWNER TO %I',
+                                     object_schema, target_role_name);
                     ELSE
                         -- Ignore other object types (index, trigger, etc.)
                         NULL;
@@ -203,23 +667,658 @@ BEGIN
         END IF;
     END LOOP;
 END;
-$$"#
+$$"#,
+            function = self.metadata.qualified_function(),
+            config_table = self.metadata.qualified_config_table(),
+            missing_role_check = MISSING_ROLE_CHECK,
+            extension_match = mapping_match_condition("extension_schema"),
+            object_match = mapping_match_condition("object_schema"),
+            precedence = MAPPING_PRECEDENCE,
+            routine_types = routine_types,
+            alter_procedure = alter_procedure
+        )
     }
 
-    pub fn create_event_trigger(&self) -> &'static str {
-        r#"CREATE EVENT TRIGGER auto_transfer_schema_ownership_trigger
+    pub fn comment_trigger_function(&self) -> String {
+        format!(
+            "COMMENT ON FUNCTION {}() IS {}",
+            self.metadata.qualified_function(),
+            quote_literal(TRIGGER_FUNCTION_MARKER)
+        )
+    }
+
+    pub fn create_event_trigger(&self) -> String {
+        format!(
+            r#"CREATE EVENT TRIGGER {}
 ON ddl_command_end
-EXECUTE FUNCTION auto_transfer_schema_ownership()"#
+EXECUTE {} {}()"#,
+            quote_identifier(&self.metadata.event_trigger),
+            self.capabilities.event_trigger_execute_keyword(),
+            self.metadata.qualified_function()
+        )
+    }
+
+    /// Function run on `sql_drop` that deactivates the mapping of a dropped schema,
+    /// so a later schema with the same name is not silently managed again. Pattern
+    /// mappings outlive the schemas they match and stay active.
+    pub fn create_cleanup_function(&self) -> String {
+        format!(
+            r#"CREATE OR REPLACE FUNCTION {function}()
+RETURNS event_trigger
+LANGUAGE plpgsql
+SECURITY DEFINER
+AS $$
+DECLARE
+    obj record;
+BEGIN
+    -- Nothing to record when the config table itself is being dropped
+    IF to_regclass({config_table_literal}) IS NULL THEN
+        RETURN;
+    END IF;
+
+    FOR obj IN SELECT * FROM pg_event_trigger_dropped_objects()
+    LOOP
+        IF obj.object_type = 'schema' THEN
+            UPDATE {config_table}
+            SET active = false,
+                dropped_at = now(),
+                dropped_by = session_user,
+                updated_at = now()
+            WHERE schema_name = obj.object_name
+              AND NOT is_pattern
+              AND active;
+        END IF;
+    END LOOP;
+END;
+$$"#,
+            function = self.metadata.qualified_cleanup_function(),
+            config_table = self.metadata.qualified_config_table(),
+            config_table_literal = quote_literal(&self.metadata.qualified_config_table())
+        )
+    }
+
+    pub fn comment_cleanup_function(&self) -> String {
+        format!(
+            "COMMENT ON FUNCTION {}() IS {}",
+            self.metadata.qualified_cleanup_function(),
+            quote_literal(CLEANUP_FUNCTION_MARKER)
+        )
+    }
+
+    pub fn create_cleanup_event_trigger(&self) -> String {
+        format!(
+            r#"CREATE EVENT TRIGGER {}
+ON sql_drop
+EXECUTE {} {}()"#,
+            quote_identifier(&self.metadata.cleanup_event_trigger),
+            self.capabilities.event_trigger_execute_keyword(),
+            self.metadata.qualified_cleanup_function()
+        )
     }
 
     pub fn insert_initial_mapping(&self) -> String {
         format!(
-            "INSERT INTO public.schema_ownership_config (schema_name, target_role) VALUES ('{}', '{}') ON CONFLICT (schema_name) DO NOTHING",
-            self.schema, self.role
+            "INSERT INTO {} (schema_name, target_role, is_pattern) VALUES ({}, {}, {}) ON CONFLICT (schema_name) DO NOTHING",
+            self.metadata.qualified_config_table(),
+            quote_literal(&self.schema),
+            quote_literal(&self.role),
+            self.schema_is_pattern
+        )
+    }
+
+    /// Points a deactivated or orphaned mapping at the requested role and makes it
+    /// active again.
+    pub fn reactivate_mapping(&self) -> String {
+        format!(
+            "UPDATE {} SET target_role = {}, active = true, dropped_at = NULL, dropped_by = NULL, updated_at = now() WHERE schema_name = {}",
+            self.metadata.qualified_config_table(),
+            quote_literal(&self.role),
+            quote_literal(&self.schema)
         )
     }
 
-    fn quote_identifier(&self, name: &str) -> String {
-        format!("\"{}\"", name.replace('"', "\"\""))
+    /// Applies the per-mapping options given on the command line to an existing
+    /// mapping. Returns `None` when no option was given.
+    pub fn update_mapping_options(&self) -> Option<String> {
+        let mut assignments = Vec::new();
+        if let Some(report) = self.options.report_extension_owner {
+            assignments.push(("report_extension_owner", report.to_string()));
+        }
+        if let Some(policy) = self.options.on_missing_role {
+            assignments.push(("on_missing_role", quote_literal(policy.as_str())));
+        }
+        if assignments.is_empty() {
+            return None;
+        }
+
+        let set_clause: Vec<String> = assignments
+            .iter()
+            .map(|(column, value)| format!("{} = {}", column, value))
+            .collect();
+        let changed_clause: Vec<String> = assignments
+            .iter()
+            .map(|(column, value)| format!("{} IS DISTINCT FROM {}", column, value))
+            .collect();
+
+        Some(format!(
+            "UPDATE {} SET {}, updated_at = now() WHERE schema_name = {} AND ({})",
+            self.metadata.qualified_config_table(),
+            set_clause.join(", "),
+            quote_literal(&self.schema),
+            changed_clause.join(" OR ")
+        ))
+    }
+
+    pub fn delete_mapping(&self) -> String {
+        format!(
+            "DELETE FROM {} WHERE schema_name = {}",
+            self.metadata.qualified_config_table(),
+            quote_literal(&self.schema)
+        )
+    }
+
+    /// Reverses the schema grants made by `init`.
+    pub fn revoke_schema_grants(&self) -> String {
+        format!(
+            "REVOKE USAGE, CREATE ON SCHEMA {} FROM {}",
+            quote_identifier(&self.schema),
+            quote_identifier(&self.role)
+        )
+    }
+
+    /// Query returning one `REVOKE` statement per relation and routine in the
+    /// schema that the role does not own. `REVOKE ... ON ALL TABLES` would also
+    /// strip an owner of the privileges on its own objects.
+    pub fn revoke_object_grants_query(&self) -> String {
+        let routine_keyword = if self.capabilities.has_procedures() { "ROUTINE" } else { "FUNCTION" };
+        format!(
+            "SELECT format('REVOKE ALL PRIVILEGES ON %s %s FROM %I',
+                          CASE WHEN c.relkind = 'S' THEN 'SEQUENCE' ELSE 'TABLE' END, c.oid::regclass, r.rolname)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_roles r ON r.rolname = {role}
+             WHERE n.nspname = {schema}
+               AND c.relkind IN ('r', 'p', 'v', 'm', 'S', 'f')
+               AND c.relowner <> r.oid
+             UNION ALL
+             SELECT format('REVOKE ALL PRIVILEGES ON {routine} %s FROM %I', p.oid::regprocedure, r.rolname)
+             FROM pg_proc p
+             JOIN pg_namespace n ON n.oid = p.pronamespace
+             JOIN pg_roles r ON r.rolname = {role}
+             WHERE n.nspname = {schema}
+               AND p.proowner <> r.oid",
+            role = quote_literal(&self.role),
+            schema = quote_literal(&self.schema),
+            routine = routine_keyword
+        )
+    }
+
+    pub fn revoke_connect(&self) -> String {
+        format!(
+            "REVOKE CONNECT ON DATABASE {} FROM {}",
+            quote_identifier(&self.database),
+            quote_identifier(&self.role)
+        )
+    }
+
+    pub fn drop_schema(&self) -> String {
+        format!("DROP SCHEMA {} CASCADE", quote_identifier(&self.schema))
+    }
+
+    pub fn drop_role(&self) -> String {
+        format!("DROP ROLE {}", quote_identifier(&self.role))
+    }
+
+    pub fn drop_config_table(&self) -> String {
+        format!(
+            "DROP TABLE IF EXISTS {}, {}",
+            self.metadata.qualified_config_table(),
+            self.metadata.qualified_history_table()
+        )
+    }
+
+    pub fn create_history_table(&self) -> String {
+        format!(
+            r#"CREATE TABLE IF NOT EXISTS {} (
+    id bigserial PRIMARY KEY,
+    schema_name name NOT NULL,
+    action text NOT NULL,
+    old_role name,
+    new_role name,
+    member name,
+    detail text,
+    changed_at timestamptz NOT NULL DEFAULT now(),
+    changed_by name NOT NULL DEFAULT session_user
+)"#,
+            self.metadata.qualified_history_table()
+        )
+    }
+
+    /// Points the mapping at `new_role` and records the change in the history table.
+    pub fn remap_mapping(&self, new_role: &str) -> Vec<String> {
+        vec![
+            format!(
+                "INSERT INTO {} (schema_name, action, old_role, new_role) VALUES ({}, 'remap', {}, {})",
+                self.metadata.qualified_history_table(),
+                quote_literal(&self.schema),
+                quote_literal(&self.role),
+                quote_literal(new_role)
+            ),
+            format!(
+                "UPDATE {} SET target_role = {}, updated_at = now() WHERE schema_name = {}",
+                self.metadata.qualified_config_table(),
+                quote_literal(new_role),
+                quote_literal(&self.schema)
+            ),
+        ]
+    }
+
+    /// Grants the target role to `member`. Before PostgreSQL 16 only the admin
+    /// option exists, and removing it takes a separate `REVOKE`.
+    pub fn grant_membership(&self, member: &str, options: &MembershipOptions) -> Vec<String> {
+        let grant = format!("GRANT {} TO {}", quote_identifier(&self.role), quote_identifier(member));
+        if self.capabilities.has_membership_options() {
+            let with = options.describe();
+            return vec![if with.is_empty() { grant } else { format!("{} WITH {}", grant, with) }];
+        }
+        match options.admin {
+            Some(true) => vec![format!("{} WITH ADMIN OPTION", grant)],
+            Some(false) => vec![
+                grant,
+                format!("REVOKE ADMIN OPTION FOR {} FROM {}", quote_identifier(&self.role), quote_identifier(member)),
+            ],
+            None => vec![grant],
+        }
+    }
+
+    /// Creates a login role with a password verifier hashed on the client, so the
+    /// plaintext never reaches the server or its logs.
+    pub fn create_login(&self, name: &str, verifier: &str, connection_limit: Option<i32>, valid_until: Option<&str>) -> String {
+        let mut sql = format!("CREATE ROLE {} LOGIN PASSWORD {}", quote_identifier(name), quote_literal(verifier));
+        if let Some(limit) = connection_limit {
+            sql.push_str(&format!(" CONNECTION LIMIT {}", limit));
+        }
+        if let Some(valid_until) = valid_until {
+            sql.push_str(&format!(" VALID UNTIL {}", quote_literal(valid_until)));
+        }
+        sql
+    }
+
+    /// Makes sessions of `login` in this database run as the target role, so
+    /// the objects it creates are owned by the role from the start.
+    pub fn set_default_role(&self, login: &str) -> String {
+        format!(
+            "ALTER ROLE {} IN DATABASE {} SET role = {}",
+            quote_identifier(login),
+            quote_identifier(&self.database),
+            quote_identifier(&self.role)
+        )
+    }
+
+    /// Lets the migrator SET ROLE to the target role without inheriting its
+    /// privileges. Before PostgreSQL 16 that takes NOINHERIT on the migrator itself.
+    pub fn grant_migrator_membership(&self, migrator: &str) -> Vec<String> {
+        if self.capabilities.has_membership_options() {
+            return self.grant_membership(
+                migrator,
+                &MembershipOptions { inherit: Some(false), set: Some(true), admin: None },
+            );
+        }
+        let mut statements = self.grant_membership(migrator, &MembershipOptions::default());
+        statements.push(format!("ALTER ROLE {} NOINHERIT", quote_identifier(migrator)));
+        statements
+    }
+
+    /// USAGE on the schema and `access` on the objects in it now.
+    pub fn grant_schema_access(&self, grantee: &str, access: SchemaAccess) -> Vec<String> {
+        let mut statements = vec![format!(
+            "GRANT USAGE ON SCHEMA {} TO {}",
+            quote_identifier(&self.schema),
+            quote_identifier(grantee)
+        )];
+        statements.extend(access.privileges(&self.capabilities).iter().map(|(kind, privileges)| {
+            format!(
+                "GRANT {} ON ALL {} IN SCHEMA {} TO {}",
+                privileges,
+                kind,
+                quote_identifier(&self.schema),
+                quote_identifier(grantee)
+            )
+        }));
+        statements
+    }
+
+    /// `access` on objects `grantor` (or, with `None`, the current user) creates later.
+    pub fn default_schema_access(&self, grantor: Option<&str>, grantee: &str, access: SchemaAccess) -> Vec<String> {
+        let for_role = grantor.map(|g| format!(" FOR ROLE {}", quote_identifier(g))).unwrap_or_default();
+        access
+            .privileges(&self.capabilities)
+            .iter()
+            .map(|(kind, privileges)| {
+                format!(
+                    "ALTER DEFAULT PRIVILEGES{} IN SCHEMA {} GRANT {} ON {} TO {}",
+                    for_role,
+                    quote_identifier(&self.schema),
+                    privileges,
+                    kind,
+                    quote_identifier(grantee)
+                )
+            })
+            .collect()
+    }
+
+    pub fn record_preset_roles(&self, roles: &PresetRoles) -> String {
+        let assignments = [
+            ("migrator_role", quote_literal(&roles.migrator)),
+            ("runtime_role", quote_literal(&roles.runtime)),
+            ("readonly_role", quote_literal(&roles.readonly)),
+        ];
+        format!(
+            "UPDATE {} SET {}, updated_at = now() WHERE schema_name = {} AND ({})",
+            self.metadata.qualified_config_table(),
+            assignments.iter().map(|(c, v)| format!("{} = {}", c, v)).collect::<Vec<_>>().join(", "),
+            quote_literal(&self.schema),
+            assignments.iter().map(|(c, v)| format!("{} IS DISTINCT FROM {}", c, v)).collect::<Vec<_>>().join(" OR ")
+        )
+    }
+
+    /// Remembers the roles whose `FOR ROLE` default privileges the tool added for
+    /// the mapping; `sync-default-privileges` only ever revokes those. Adds the
+    /// column first, as commands other than `init` may meet an older table.
+    pub fn record_default_privilege_grantors(&self, grantors: &[String]) -> Vec<String> {
+        vec![
+            self.add_config_column("default_privilege_grantors", "name[]"),
+            format!(
+                "UPDATE {} SET default_privilege_grantors = ARRAY(SELECT DISTINCT g FROM unnest(COALESCE(default_privilege_grantors, '{{}}') || {}) g ORDER BY 1) WHERE schema_name = {}",
+                self.metadata.qualified_config_table(),
+                name_array(grantors),
+                quote_literal(&self.schema)
+            ),
+        ]
+    }
+
+    /// Records every current (recursive) member of the role as a grantor, for use
+    /// with `alter_default_privileges_for_members_query` where members are not
+    /// known in advance. Expects the column to exist.
+    pub fn record_member_default_privilege_grantors(&self) -> String {
+        format!(
+            "{}
+             UPDATE {} SET default_privilege_grantors = ARRAY(
+                 SELECT DISTINCT g
+                 FROM unnest(COALESCE(default_privilege_grantors, '{{}}')
+                             || ARRAY(SELECT r.rolname FROM members JOIN pg_roles r ON r.oid = members.member)) g
+                 ORDER BY 1)
+             WHERE schema_name = {}",
+            self.members_cte(),
+            self.metadata.qualified_config_table(),
+            quote_literal(&self.schema)
+        )
+    }
+
+    pub fn forget_default_privilege_grantors(&self, grantors: &[String]) -> Vec<String> {
+        vec![
+            self.add_config_column("default_privilege_grantors", "name[]"),
+            format!(
+                "UPDATE {} SET default_privilege_grantors = ARRAY(SELECT g FROM unnest(default_privilege_grantors) g WHERE g <> ALL ({}) ORDER BY 1) WHERE schema_name = {} AND default_privilege_grantors IS NOT NULL",
+                self.metadata.qualified_config_table(),
+                name_array(grantors),
+                quote_literal(&self.schema)
+            ),
+        ]
+    }
+
+    pub fn revoke_membership(&self, member: &str) -> String {
+        format!("REVOKE {} FROM {}", quote_identifier(&self.role), quote_identifier(member))
+    }
+
+    /// Records in the history table that `uninstall` removed the mapping.
+    pub fn record_uninstall(&self) -> String {
+        format!(
+            "INSERT INTO {} (schema_name, action, old_role) VALUES ({}, 'uninstall', {})",
+            self.metadata.qualified_history_table(),
+            quote_literal(&self.schema),
+            quote_literal(&self.role)
+        )
+    }
+
+    /// Records a `grant-member` or `revoke-member` in the history table.
+    pub fn record_membership(&self, action: &str, member: &str, detail: &str) -> String {
+        format!(
+            "INSERT INTO {} (schema_name, action, new_role, member, detail) VALUES ({}, {}, {}, {}, {})",
+            self.metadata.qualified_history_table(),
+            quote_literal(&self.schema),
+            quote_literal(action),
+            quote_literal(&self.role),
+            quote_literal(member),
+            if detail.is_empty() { "NULL".to_string() } else { quote_literal(detail) }
+        )
+    }
+
+    /// Revokes everything granted to the role on the schema's objects. Only safe
+    /// once the role owns nothing in the schema, as it would also strip an owner's
+    /// privileges on its own objects.
+    pub fn revoke_all_in_schema(&self) -> Vec<String> {
+        ["TABLES", "SEQUENCES", "FUNCTIONS"]
+            .iter()
+            .map(|kind| {
+                format!(
+                    "REVOKE ALL PRIVILEGES ON ALL {} IN SCHEMA {} FROM {}",
+                    kind,
+                    quote_identifier(&self.schema),
+                    quote_identifier(&self.role)
+                )
+            })
+            .collect()
+    }
+
+    /// Moves an existing config table (and its history, if any) into the
+    /// configured schema and name.
+    pub fn migrate_config_table(&self, from: &MetadataNames) -> Vec<String> {
+        let mut statements = Vec::new();
+        if from.schema != self.metadata.schema {
+            statements.push(format!(
+                "ALTER TABLE {} SET SCHEMA {}",
+                from.qualified_config_table(),
+                quote_identifier(&self.metadata.schema)
+            ));
+            statements.push(format!(
+                "ALTER TABLE IF EXISTS {} SET SCHEMA {}",
+                from.qualified_history_table(),
+                quote_identifier(&self.metadata.schema)
+            ));
+        }
+        if from.config_table != self.metadata.config_table {
+            statements.push(format!(
+                "ALTER TABLE {}.{} RENAME TO {}",
+                quote_identifier(&self.metadata.schema),
+                quote_identifier(&from.config_table),
+                quote_identifier(&self.metadata.config_table)
+            ));
+            statements.push(format!(
+                "ALTER TABLE IF EXISTS {}.{} RENAME TO {}",
+                quote_identifier(&self.metadata.schema),
+                quote_identifier(&from.history_table()),
+                quote_identifier(&self.metadata.history_table())
+            ));
+        }
+        statements
+    }
+
+    pub fn drop_event_triggers(&self, names: &MetadataNames) -> Vec<String> {
+        vec![
+            format!("DROP EVENT TRIGGER IF EXISTS {}", quote_identifier(&names.event_trigger)),
+            format!("DROP EVENT TRIGGER IF EXISTS {}", quote_identifier(&names.cleanup_event_trigger)),
+        ]
+    }
+
+    pub fn drop_trigger_functions(&self, names: &MetadataNames) -> Vec<String> {
+        vec![
+            format!("DROP FUNCTION IF EXISTS {}()", names.qualified_function()),
+            format!("DROP FUNCTION IF EXISTS {}()", names.qualified_cleanup_function()),
+        ]
+    }
+}
+
+/// `ALTER <keyword> ... OWNER TO` keyword for a `pg_identify_object` type.
+pub fn alter_owner_keyword(object_type: &str) -> Option<&'static str> {
+    Some(match object_type {
+        "schema" => "SCHEMA",
+        "table" => "TABLE",
+        "sequence" => "SEQUENCE",
+        "view" => "VIEW",
+        "materialized view" => "MATERIALIZED VIEW",
+        "foreign table" => "FOREIGN TABLE",
+        "function" => "FUNCTION",
+        "procedure" => "PROCEDURE",
+        "aggregate" => "AGGREGATE",
+        "type" => "TYPE",
+        "collation" => "COLLATION",
+        "conversion" => "CONVERSION",
+        "operator" => "OPERATOR",
+        "operator class" => "OPERATOR CLASS",
+        "operator family" => "OPERATOR FAMILY",
+        "text search configuration" => "TEXT SEARCH CONFIGURATION",
+        "text search dictionary" => "TEXT SEARCH DICTIONARY",
+        "statistics object" => "STATISTICS",
+        _ => return None,
+    })
+}
+
+/// Ownership change for an object listed by `catalog::owned_objects`; `identity`
+/// is already quoted. Returns `None` for types with no `ALTER ... OWNER TO`.
+pub fn alter_object_owner(object_type: &str, identity: &str, role: &str) -> Option<String> {
+    alter_owner_keyword(object_type)
+        .map(|keyword| format!("ALTER {} {} OWNER TO {}", keyword, identity, quote_identifier(role)))
+}
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn name_array(names: &[String]) -> String {
+    format!(
+        "ARRAY[{}]::name[]",
+        names.iter().map(|n| quote_literal(n)).collect::<Vec<_>>().join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORTED_VERSIONS: [i32; 9] = [100023, 110022, 120022, 130018, 140015, 150010, 160006, 170002, 180000];
+
+    fn templates_for(version_num: i32) -> SqlTemplates {
+        SqlTemplates::new(
+            "appdb".to_string(),
+            "app".to_string(),
+            "app_owner".to_string(),
+            MetadataNames::default(),
+            ServerCapabilities::from_version_num(version_num),
+        )
+    }
+
+    #[test]
+    fn event_triggers_use_execute_procedure_before_11() {
+        for version in SUPPORTED_VERSIONS {
+            let templates = templates_for(version);
+            let expected = if version < 110000 { "EXECUTE PROCEDURE" } else { "EXECUTE FUNCTION" };
+
+            for sql in [templates.create_event_trigger(), templates.create_cleanup_event_trigger()] {
+                assert!(sql.contains(expected), "PG {}: {}", version / 10000, sql);
+            }
+        }
+    }
+
+    #[test]
+    fn trigger_function_handles_procedures_from_11() {
+        for version in SUPPORTED_VERSIONS {
+            let sql = templates_for(version).create_trigger_function();
+            assert_eq!(
+                sql.contains("ALTER PROCEDURE"),
+                version >= 110000,
+                "PG {}",
+                version / 10000
+            );
+            assert!(sql.contains("ALTER FUNCTION"));
+        }
+    }
+
+    #[test]
+    fn public_schema_create_revoked_before_15() {
+        for version in SUPPORTED_VERSIONS {
+            let sql = templates_for(version).revoke_public_schema_create();
+            assert_eq!(sql.is_some(), version < 150000, "PG {}", version / 10000);
+        }
+    }
+
+    #[test]
+    fn membership_options_from_16() {
+        let options = MembershipOptions { inherit: None, set: None, admin: Some(false) };
+        for version in SUPPORTED_VERSIONS {
+            let sql = templates_for(version).grant_membership("dev", &options);
+            if version >= 160000 {
+                assert_eq!(sql, vec!["GRANT \"app_owner\" TO \"dev\" WITH ADMIN FALSE"], "PG {}", version / 10000);
+            } else {
+                assert_eq!(sql.len(), 2, "PG {}", version / 10000);
+                assert!(sql[1].starts_with("REVOKE ADMIN OPTION FOR"));
+            }
+
+            // The migrator never inherits the owner's privileges
+            let sql = templates_for(version).grant_migrator_membership("migrator").join("; ");
+            assert!(sql.contains(if version >= 160000 { "INHERIT FALSE" } else { "NOINHERIT" }), "PG {}", version / 10000);
+        }
+    }
+
+    #[test]
+    fn runtime_access_includes_maintain_from_17() {
+        for version in SUPPORTED_VERSIONS {
+            let templates = templates_for(version);
+            let runtime = templates.grant_schema_access("app_runtime", SchemaAccess::ReadWrite);
+            let defaults = templates.default_schema_access(Some("dev"), "app_runtime", SchemaAccess::ReadWrite);
+            for sql in [&runtime[1], &defaults[0]] {
+                assert_eq!(sql.contains("DELETE, MAINTAIN ON"), version >= 170000, "PG {}: {}", version / 10000, sql);
+            }
+
+            let readonly = templates.grant_schema_access("app_readonly", SchemaAccess::ReadOnly).join("; ");
+            assert!(!readonly.contains("MAINTAIN"), "PG {}", version / 10000);
+        }
+    }
+
+    #[test]
+    fn statements_identical_across_versions_where_unaffected() {
+        let baseline = templates_for(SUPPORTED_VERSIONS[0]);
+        for version in SUPPORTED_VERSIONS {
+            let templates = templates_for(version);
+            assert_eq!(templates.create_config_table(), baseline.create_config_table());
+            assert_eq!(templates.grant_all_functions(), baseline.grant_all_functions());
+            assert_eq!(
+                templates.alter_default_privileges_for_role("dev"),
+                baseline.alter_default_privileges_for_role("dev")
+            );
+        }
+    }
+
+    #[test]
+    fn alter_object_owner_uses_type_keyword() {
+        assert_eq!(
+            alter_object_owner("materialized view", "app.mv", "app_owner").as_deref(),
+            Some(r#"ALTER MATERIALIZED VIEW app.mv OWNER TO "app_owner""#)
+        );
+        assert_eq!(
+            alter_object_owner("operator class", "app.oc USING btree", "app_owner").as_deref(),
+            Some(r#"ALTER OPERATOR CLASS app.oc USING btree OWNER TO "app_owner""#)
+        );
+        assert_eq!(alter_object_owner("index", "app.idx", "app_owner"), None);
+    }
+
+    #[test]
+    fn identifiers_and_literals_are_escaped() {
+        assert_eq!(quote_identifier(r#"we"ird"#), r#""we""ird""#);
+        assert_eq!(quote_literal("o'brien"), "'o''brien'");
     }
 }